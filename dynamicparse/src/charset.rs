@@ -0,0 +1,76 @@
+use crate::{Category, Expected, ParseError, Parser};
+use parsecore::Member;
+use std::ops::RangeBounds;
+use std::rc::Rc;
+
+/// A set of characters, built from single characters, ranges and
+/// `Category`s; `make` turns it into a parser of any one character in it.
+///
+/// The set itself is `parsecore::CharSet`, shared with `staticparse`.
+#[derive(Debug, Clone, Default)]
+pub struct CharSet(parsecore::CharSet);
+
+impl CharSet {
+    pub fn new() -> Self {
+        CharSet::default()
+    }
+
+    pub fn char(self, c: char) -> Self {
+        CharSet(self.0.char(c))
+    }
+
+    pub fn chars(self, chars: &str) -> Self {
+        CharSet(self.0.chars(chars))
+    }
+
+    /// Every character in `r`; a range with nothing in it adds nothing.
+    pub fn range<R: RangeBounds<char>>(self, r: R) -> Self {
+        CharSet(self.0.range(r))
+    }
+
+    pub fn category(self, category: Category) -> Self {
+        CharSet(self.0.category(category))
+    }
+
+    /// The complement: every character this set doesn't contain.
+    pub fn negate(self) -> Self {
+        CharSet(self.0.negate())
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.0.contains(c)
+    }
+
+    pub fn make<'a>(self) -> Parser<'a, char> {
+        Rc::new(move |s| match s.rest().chars().next() {
+            Some(c) if self.contains(c) => Ok((c, s.advance(c.len_utf8()))),
+            Some(_) => Err(ParseError::new(s.offset(), self.expected())),
+            None => Err(s.exhausted(1, self.expected())),
+        })
+    }
+
+    /// What a failure to match reports.
+    fn expected(&self) -> Vec<Expected> {
+        self.0
+            .expected()
+            .iter()
+            .map(|&member| member.into())
+            .collect()
+    }
+}
+
+impl From<&str> for CharSet {
+    fn from(chars: &str) -> Self {
+        CharSet::new().chars(chars)
+    }
+}
+
+impl From<Member> for Expected {
+    fn from(member: Member) -> Self {
+        match member {
+            Member::Char(c) => Expected::Char(c),
+            Member::Range(lo, hi) => Expected::Range(lo, hi),
+            Member::Category(category) => Expected::Label(category.name()),
+        }
+    }
+}
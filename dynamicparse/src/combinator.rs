@@ -1,14 +1,27 @@
-use crate::Parser;
+use crate::{Expected, ParseError, ParseResult, Parser, Spanned, State};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
 
 pub fn and<'a, T: 'a, U: 'a>(parser1: Parser<'a, T>, parser2: Parser<'a, U>) -> Parser<'a, (T, U)> {
-    Rc::new(move |s| parser1(s).and_then(|(v1, r1)| parser2(r1).map(|(v2, r2)| ((v1, v2), r2))))
+    Rc::new(move |s| {
+        let (v1, r1) = parser1(s)?;
+        let (v2, r2) = parser2(r1.clone()).map_err(|e| r1.fail(e))?;
+        Ok(((v1, v2), r2))
+    })
 }
 
 pub fn or<'a, T: 'a>(parser1: Parser<'a, T>, parser2: Parser<'a, T>) -> Parser<'a, T> {
-    Rc::new(move |s| match parser1(s) {
-        r @ Some(_) => r,
-        _ => parser2(s),
+    Rc::new(move |s| match parser1(s.clone()) {
+        r @ Ok(_) => r,
+        Err(e1) if e1.is_fatal() => Err(e1),
+        Err(e1) => match parser2(s.clone()) {
+            // The second branch consumed nothing, so what the first one
+            // expected could still come next.
+            Ok((v, r)) if r.offset() == s.offset() => Ok((v, r.backtrack(e1)?)),
+            Ok(r) => Ok(r),
+            Err(e2) => Err(e1.merge(e2)),
+        },
     })
 }
 
@@ -16,16 +29,32 @@ pub fn many<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, Vec<T>> {
     Rc::new(move |s| {
         let mut input = s;
         let mut vs = Vec::new();
-        while let Some((v, r)) = parser(input) {
-            vs.push(v);
-            input = r;
+        loop {
+            match parser(input.clone()) {
+                Ok((v, r)) => {
+                    vs.push(v);
+                    input = r;
+                }
+                Err(e) => return Ok((vs, input.backtrack(e)?)),
+            }
         }
-        Some((vs, input))
     })
 }
 
 pub fn many1<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, Vec<T>> {
-    filter(many(parser.clone()), |vs| !vs.is_empty())
+    Rc::new(move |s| {
+        let (v, mut input) = parser(s)?;
+        let mut vs = vec![v];
+        loop {
+            match parser(input.clone()) {
+                Ok((v, r)) => {
+                    vs.push(v);
+                    input = r;
+                }
+                Err(e) => return Ok((vs, input.backtrack(e)?)),
+            }
+        }
+    })
 }
 
 pub fn skip<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, ()> {
@@ -39,34 +68,87 @@ pub fn between<'a, T: 'a, U: 'a, V: 'a>(
 ) -> Parser<'a, T> {
     Rc::new(move |s| {
         let (_, r) = start(s)?;
-        let (v, r) = parser(r)?;
-        let (_, r) = end(r)?;
-        Some((v, r))
+        let (v, r) = parser(r.clone()).map_err(|e| r.fail(e))?;
+        let (_, r) = end(r.clone()).map_err(|e| r.fail(e))?;
+        Ok((v, r))
     })
 }
 
 pub fn sep_by<'a, T: 'a, U: 'a>(parser: Parser<'a, T>, sep: Parser<'a, U>) -> Parser<'a, Vec<T>> {
     Rc::new(move |s| {
-        let mut vs = Vec::new();
-        let mut input = s;
-
-        if let Some((v, r)) = parser(input) {
-            vs.push(v);
-            input = r;
-        } else {
-            return None;
+        let (v, mut input) = parser(s)?;
+        let mut vs = vec![v];
+        loop {
+            // A separator without an element after it stays unconsumed.
+            let element =
+                sep(input.clone()).and_then(|(_, r1)| parser(r1.clone()).map_err(|e| r1.fail(e)));
+            match element {
+                Ok((v, r)) => {
+                    vs.push(v);
+                    input = r;
+                }
+                Err(e) => return Ok((vs, input.backtrack(e)?)),
+            }
         }
+    })
+}
 
-        while let Some((_, r1)) = sep(input) {
-            if let Some((v, r2)) = parser(r1) {
-                vs.push(v);
-                input = r2;
-            } else {
-                break;
+/// One or more `parser`s separated by `op`, folded from the left with the
+/// functions `op` returns: `1 - 2 - 3` is `(1 - 2) - 3`.
+pub fn chain_left<'a, T: 'a, F>(parser: Parser<'a, T>, op: Parser<'a, F>) -> Parser<'a, T>
+where
+    F: Fn(T, T) -> T + 'a,
+{
+    Rc::new(move |s| {
+        let (mut acc, mut input) = parser(s)?;
+        loop {
+            match operation(&op, &parser, input.clone()) {
+                Ok(((f, v), r)) => {
+                    acc = f(acc, v);
+                    input = r;
+                }
+                Err(e) => return Ok((acc, input.backtrack(e)?)),
             }
         }
+    })
+}
 
-        Some((vs, input))
+/// An operator and the operand after it, for `chain_left` and `chain_right`.
+fn operation<'a, T, F>(
+    op: &Parser<'a, F>,
+    parser: &Parser<'a, T>,
+    s: State<'a>,
+) -> ParseResult<'a, (F, T)> {
+    let (f, r) = op(s)?;
+    let (v, r) = parser(r.clone()).map_err(|e| r.fail(e))?;
+    Ok(((f, v), r))
+}
+
+/// Like `chain_left`, but folded from the right: `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`.
+pub fn chain_right<'a, T: 'a, F>(parser: Parser<'a, T>, op: Parser<'a, F>) -> Parser<'a, T>
+where
+    F: Fn(T, T) -> T + 'a,
+{
+    Rc::new(move |s| {
+        let (mut last, mut input) = parser(s)?;
+        let mut pending = Vec::new();
+        loop {
+            match operation(&op, &parser, input.clone()) {
+                Ok(((f, v), r)) => {
+                    pending.push((std::mem::replace(&mut last, v), f));
+                    input = r;
+                }
+                Err(e) => {
+                    input = input.backtrack(e)?;
+                    break;
+                }
+            }
+        }
+        let folded = pending
+            .into_iter()
+            .rev()
+            .fold(last, |acc, (v, f)| f(v, acc));
+        Ok((folded, input))
     })
 }
 
@@ -74,7 +156,15 @@ pub fn filter<'a, T: 'a, F>(parser: Parser<'a, T>, pred: F) -> Parser<'a, T>
 where
     F: Fn(&T) -> bool + 'a,
 {
-    Rc::new(move |s| parser(s).filter(|(v, _)| pred(v)))
+    Rc::new(move |s| {
+        let offset = s.offset();
+        let (v, r) = parser(s)?;
+        if pred(&v) {
+            Ok((v, r))
+        } else {
+            Err(ParseError::unexpected(offset))
+        }
+    })
 }
 
 pub fn map<'a, T: 'a, U, F>(parser: Parser<'a, T>, func: F) -> Parser<'a, U>
@@ -85,10 +175,9 @@ where
 }
 
 pub fn optional<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, Option<T>> {
-    Rc::new(move |s| {
-        parser(s)
-            .map(|(v, r)| (Some(v), r))
-            .or_else(|| Some((None, s)))
+    Rc::new(move |s| match parser(s.clone()) {
+        Ok((v, r)) => Ok((Some(v), r)),
+        Err(e) => Ok((None, s.backtrack(e)?)),
     })
 }
 
@@ -97,10 +186,541 @@ pub fn except<'a, T: PartialEq + 'a>(
     parser2: Parser<'a, T>,
 ) -> Parser<'a, T> {
     Rc::new(move |s| {
-        let (v1, r) = parser1(s)?;
-        if parser2(s).map_or(false, |(v2, _)| v1 == v2) {
-            return None;
+        let (v1, r) = parser1(s.clone())?;
+        match parser2(s.clone()) {
+            Ok((v2, _)) if v1 == v2 => Err(ParseError::unexpected(s.offset())),
+            Err(e) if e.is_incomplete() => Err(e),
+            _ => Ok((v1, r)),
         }
-        Some((v1, r))
     })
 }
+
+/// Commits to `parser`: if it fails, enclosing `or`, `optional`, `many` and
+/// `sep_by` report the failure instead of trying something else.
+pub fn cut<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, T> {
+    Rc::new(move |s| parser(s).map_err(ParseError::commit))
+}
+
+/// Names `parser` in error messages: a failure that didn't get past the start
+/// of the input reports `expected <name>` instead of the raw expectations of
+/// its parts, and one right where it ended reports what was expected
+/// `after <name>`.
+pub fn label<'a, T: 'a>(parser: Parser<'a, T>, name: &'static str) -> Parser<'a, T> {
+    let labelled = expect(parser, Expected::Label(name));
+    Rc::new(move |s| {
+        let offset = s.offset();
+        let (v, r) = labelled(s)?;
+        if r.offset() > offset {
+            return Ok((v, r.after(name)));
+        }
+        Ok((v, r))
+    })
+}
+
+/// Adds `name` to the breadcrumb trail of failures that happen after `parser`
+/// has consumed some input.
+pub fn context<'a, T: 'a>(parser: Parser<'a, T>, name: &'static str) -> Parser<'a, T> {
+    Rc::new(move |s| {
+        let offset = s.offset();
+        parser(s).map_err(|e| {
+            if e.offset() > offset {
+                e.within(name)
+            } else {
+                e
+            }
+        })
+    })
+}
+
+/// The slice of the input `parser` matched, instead of its value.
+pub fn recognize<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, &'a str> {
+    Rc::new(move |s| {
+        let (src, offset) = (s.source(), s.offset());
+        let (_, r) = parser(s)?;
+        Ok((&src[offset..r.offset()], r))
+    })
+}
+
+/// The slice of the input `parser` matched, along with its value.
+pub fn consumed<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, (&'a str, T)> {
+    Rc::new(move |s| {
+        let (src, offset) = (s.source(), s.offset());
+        let (v, r) = parser(s)?;
+        Ok(((&src[offset..r.offset()], v), r))
+    })
+}
+
+pub fn spanned<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, Spanned<T>> {
+    Rc::new(move |s| {
+        let (line, column) = s.line_column();
+        let start = s.offset();
+        let (value, r) = parser(s)?;
+        let spanned = Spanned {
+            value,
+            start,
+            end: r.offset(),
+            line,
+            column,
+        };
+        Ok((spanned, r))
+    })
+}
+
+pub(crate) fn expect<'a, T: 'a>(parser: Parser<'a, T>, expected: Expected) -> Parser<'a, T> {
+    Rc::new(move |s| {
+        let offset = s.offset();
+        match parser(s) {
+            Ok((v, r)) => Ok((v, r.relabel(offset, &expected))),
+            Err(e) if e.offset() == offset => Err(e.expecting(expected.clone())),
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// Builds a rule that can refer to itself: `define` gets a parser standing in
+/// for the finished one and is called exactly once. The stand-in only holds a
+/// weak reference, so the rule doesn't keep itself alive.
+pub fn recursive<'a, T: 'a>(define: impl FnOnce(Parser<'a, T>) -> Parser<'a, T>) -> Parser<'a, T> {
+    let slot: Rc<OnceCell<Parser<'a, T>>> = Rc::new(OnceCell::new());
+    let weak = Rc::downgrade(&slot);
+    let _ = slot.set(define(Rc::new(move |s| {
+        let slot = weak
+            .upgrade()
+            .expect("recursive parser used after its rule was dropped");
+        let rule = slot
+            .get()
+            .expect("recursive parser used while its rule was being defined");
+        rule(s)
+    })));
+    Rc::new(move |s| slot.get().unwrap()(s))
+}
+
+/// Defers calling `make` until the parser is first run, then keeps what it
+/// built, so rule functions can mention each other.
+pub fn lazy<'a, T: 'a>(make: impl Fn() -> Parser<'a, T> + 'a) -> Parser<'a, T> {
+    let slot = OnceCell::new();
+    Rc::new(move |s| slot.get_or_init(&make)(s))
+}
+
+/// What a memoized parser found at some offset: its value and end offset, or
+/// its error. `active` entries belong to a parse that's still running there;
+/// reaching one again means the rule is left recursive at that offset.
+struct MemoEntry<T> {
+    result: Result<(T, usize, Option<Rc<ParseError>>), ParseError>,
+    active: bool,
+    recursed: bool,
+}
+
+impl<T: Clone> MemoEntry<T> {
+    fn new(result: &ParseResult<'_, T>, active: bool) -> Self {
+        MemoEntry {
+            result: match result {
+                Ok((v, r)) => Ok((v.clone(), r.offset(), r.hint())),
+                Err(e) => Err(e.clone()),
+            },
+            active,
+            recursed: false,
+        }
+    }
+
+    fn replay<'a>(&self, s: State<'a>) -> ParseResult<'a, T> {
+        match &self.result {
+            Ok((v, end, hint)) => {
+                let offset = s.offset();
+                Ok((v.clone(), s.advance(end - offset).with_hint(hint.clone())))
+            }
+            Err(e) => Err(e.clone()),
+        }
+    }
+}
+
+/// Remembers `parser`'s result at every offset it's tried at, for the rest of
+/// the parse run, so trying it again at the same place costs a lookup instead
+/// of a reparse.
+///
+/// This also lets a rule call itself first thing, as in
+/// `expr := expr '-' term | term`: the recursive call fails at first, and the
+/// rule then reruns with each result standing in for it for as long as that
+/// gets further, so the parse is left associative. Memoize inside `recursive`
+/// so the recursive call goes through the table.
+///
+/// Only the memoized rule itself is regrown. When left recursion goes through
+/// other rules first, as in `a := b 'x' | 'y'` with `b := a 'z'`, memoize
+/// just one rule of the cycle: a memoized `b` would keep the result of its
+/// first try, and `a` would stop after the `'y'`.
+pub fn memo<'a, T: Clone + 'a>(parser: Parser<'a, T>) -> Parser<'a, T> {
+    let run = Cell::new(usize::MAX);
+    let table = RefCell::new(HashMap::<usize, MemoEntry<T>>::new());
+    Rc::new(move |s| {
+        if run.get() != s.run() {
+            run.set(s.run());
+            table.borrow_mut().clear();
+        }
+        if let Some(entry) = table.borrow_mut().get_mut(&s.offset()) {
+            entry.recursed |= entry.active;
+            return entry.replay(s);
+        }
+        let seed = Err(ParseError::unexpected(s.offset()));
+        table
+            .borrow_mut()
+            .insert(s.offset(), MemoEntry::new(&seed, true));
+        let mut result = parser(s.clone());
+        if table.borrow()[&s.offset()].recursed {
+            while let Ok((_, r)) = &result {
+                let end = r.offset();
+                table
+                    .borrow_mut()
+                    .insert(s.offset(), MemoEntry::new(&result, true));
+                match parser(s.clone()) {
+                    Ok((v, r)) if r.offset() > end => result = Ok((v, r)),
+                    Err(e) if e.is_fatal() => result = Err(e),
+                    _ => break,
+                }
+            }
+        }
+        table
+            .borrow_mut()
+            .insert(s.offset(), MemoEntry::new(&result, false));
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::*;
+    use crate::unicode::GeneralCategory;
+    use crate::{line_column, parse, parse_partial, Category, CharSet, Status};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_spanned() {
+        let ws = take_while(|c| c.is_whitespace());
+        let word = spanned(take_while1(|c| c.is_alphabetic()));
+        let words = many(map(and(ws, word), |(_, w)| w));
+        let (spans, _) = parse(&words, "\n  hi\r\n\tthere").unwrap();
+        assert_eq!("hi", spans[0].value);
+        assert_eq!((3, 5), (spans[0].start, spans[0].end));
+        assert_eq!((2, 3), (spans[0].line, spans[0].column));
+        assert_eq!("there", spans[1].value);
+        assert_eq!((8, 13), (spans[1].start, spans[1].end));
+        assert_eq!((3, 2), (spans[1].line, spans[1].column));
+        assert_eq!((3, 7), line_column("\n  hi\r\n\tthere", 13));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse(&and(digit(), character('x')), "12").unwrap_err();
+        assert_eq!(
+            (1, &[Expected::Char('x')][..]),
+            (err.offset(), err.expected())
+        );
+        let sign = or(character('+'), or(character('-'), map(tag("±"), |_| '±')));
+        let err = parse(&sign, "1").unwrap_err();
+        assert_eq!(
+            &[
+                Expected::Char('+'),
+                Expected::Char('-'),
+                Expected::Token("±".to_owned())
+            ],
+            err.expected()
+        );
+        let list = between(
+            sep_by(digit(), character(',')),
+            character('['),
+            character(']'),
+        );
+        let err = parse(&list, "(1]").unwrap_err();
+        assert_eq!(
+            (0, &[Expected::Char('[')][..]),
+            (err.offset(), err.expected())
+        );
+        let err = parse(&list, "[1,x]").unwrap_err();
+        assert_eq!(
+            (3, &[Expected::Label("digit")][..]),
+            (err.offset(), err.expected())
+        );
+        let err = parse(&list, "[1x").unwrap_err();
+        assert_eq!(
+            (2, &[Expected::Char(','), Expected::Char(']')][..]),
+            (err.offset(), err.expected())
+        );
+        let digits = and(many(digit()), character(';'));
+        let err = parse(&digits, "12x").unwrap_err();
+        assert_eq!(
+            &[Expected::Label("digit"), Expected::Char(';')],
+            err.expected()
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let ws = take_while(|c| c.is_whitespace());
+        let item = label(map(and(ws, many1(digit())), |(_, ds)| ds), "number");
+        let list = context(
+            between(sep_by(item, character(',')), character('['), character(']')),
+            "list",
+        );
+        let src = "[1,\n\t23x]";
+        let err = parse(&list, src).unwrap_err();
+        assert_eq!((7, Some("number")), (err.offset(), err.after()));
+        assert_eq!(
+            "expected ',' or ']' after number, found 'x'",
+            err.message(src)
+        );
+        assert_eq!(
+            "expected ',' or ']' after number at offset 7 (in list)",
+            err.to_string()
+        );
+        let expected = concat!(
+            "error: expected ',' or ']' after number, found 'x'\n",
+            " --> 2:4\n",
+            "  |\n",
+            "2 | \t23x]\n",
+            "  | \t  ^\n",
+            "  = note: in list\n",
+        );
+        assert_eq!(expected, err.render(src));
+        let err = parse(&list, "[1,]").unwrap_err();
+        assert_eq!(
+            "error: expected number, found ']'\n --> 1:4\n  |\n1 | [1,]\n  |    ^\n  = note: in list\n",
+            err.render("[1,]")
+        );
+    }
+
+    #[test]
+    fn test_label_context() {
+        let number = label(many1(digit()), "number");
+        let err = parse(&number, "x").unwrap_err();
+        assert_eq!(&[Expected::Label("number")], err.expected());
+        let digits = and(label(many(digit()), "digits"), character(';'));
+        let err = parse(&digits, "x").unwrap_err();
+        assert_eq!(
+            &[Expected::Label("digits"), Expected::Char(';')],
+            err.expected()
+        );
+
+        let entry = context(and(and(number.clone(), character(':')), number), "entry");
+        let table = context(
+            between(
+                sep_by(entry, character(',')),
+                character('{'),
+                character('}'),
+            ),
+            "table",
+        );
+        let err = parse(&table, "{1:2,3:x}").unwrap_err();
+        assert_eq!(7, err.offset());
+        assert_eq!(&[Expected::Label("number")], err.expected());
+        assert_eq!(&["table", "entry"], err.context());
+        assert_eq!(
+            "expected number at offset 7 (in table > in entry)",
+            err.to_string()
+        );
+        let err = parse(&table, "{1:2,x}").unwrap_err();
+        assert_eq!(5, err.offset());
+        assert_eq!(&["table"], err.context());
+    }
+
+    #[test]
+    fn test_cut() {
+        let binding = |name| map(and(tag("let "), name), |(_, n)| n);
+        let statement = or(binding(identifier()), identifier());
+        assert_eq!(Ok(("let", " 1")), parse(&statement, "let 1"));
+        let statement = or(binding(cut(identifier())), identifier());
+        let err = parse(&statement, "let 1").unwrap_err();
+        assert!(err.is_committed());
+        assert_eq!(4, err.offset());
+        assert_eq!(&[Expected::Label("identifier")], err.expected());
+
+        let tagged = |digit| many(map(and(character('#'), digit), |(_, d)| d));
+        assert_eq!(Ok((vec![1], "#x")), parse(&tagged(digit()), "#1#x"));
+        let err = parse(&tagged(cut(digit())), "#1#x").unwrap_err();
+        assert!(err.is_committed());
+        assert_eq!(3, err.offset());
+        let err = parse(&optional(tagged(cut(digit()))), "#x").unwrap_err();
+        assert_eq!(1, err.offset());
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let keyword = tag("true");
+        assert_eq!(
+            Ok(Status::Incomplete { needed: 2 }),
+            parse_partial(&keyword, "tr")
+        );
+        let digits = many1(digit());
+        assert_eq!(
+            Ok(Status::Incomplete { needed: 1 }),
+            parse_partial(&digits, "12")
+        );
+        assert_eq!(
+            Ok(Status::Done(vec![1, 2], ";")),
+            parse_partial(&digits, "12;")
+        );
+        assert!(parse_partial(&digits, "x").is_err());
+        assert_eq!(Ok((vec![1, 2], "")), parse(&digits, "12"));
+    }
+
+    fn even<'a>() -> Parser<'a, usize> {
+        or(
+            map(and(character('a'), lazy(odd)), |(_, n)| n + 1),
+            map(character('.'), |_| 0),
+        )
+    }
+
+    fn odd<'a>() -> Parser<'a, usize> {
+        map(and(character('a'), lazy(even)), |(_, n)| n + 1)
+    }
+
+    #[test]
+    fn test_recursive() {
+        let depth = recursive(|parens| {
+            map(
+                and(and(character('('), optional(parens)), character(')')),
+                |((_, inner), _)| inner.map_or(1, |d: usize| d + 1),
+            )
+        });
+        assert_eq!(Ok((3, "()")), parse(&depth, "((()))()"));
+        assert!(parse(&depth, "(()").is_err());
+    }
+
+    #[test]
+    fn test_lazy() {
+        assert_eq!(Ok((4, "")), parse(&even(), "aaaa."));
+        assert!(parse(&even(), "aaa.").is_err());
+        assert_eq!(Ok((3, "")), parse(&odd(), "aaa."));
+    }
+
+    #[test]
+    fn test_memo() {
+        let calls = Cell::new(0);
+        let letters = memo(take_while(|c: &char| {
+            calls.set(calls.get() + 1);
+            c.is_ascii_lowercase()
+        }));
+        let words = or(
+            and(letters.clone(), character('!')),
+            and(letters, character('?')),
+        );
+        assert!(parse(&words, "abc?").is_ok());
+        assert_eq!(4, calls.get());
+        assert!(parse(&words, "abc?").is_ok());
+        assert_eq!(8, calls.get());
+    }
+
+    #[test]
+    fn test_left_recursion() {
+        let number = map(many1(digit()), |ds| {
+            ds.iter().fold(0, |n, &d| n * 10 + d as i64)
+        });
+        let op = |c: char, f: fn(i64, i64) -> i64| map(character(c), move |_| f);
+        let expr = recursive(|expr| {
+            let parens = map(
+                and(and(character('('), expr.clone()), character(')')),
+                |((_, v), _)| v,
+            );
+            let factor = or(number, parens);
+            let term = recursive(|term| {
+                let mul = or(op('*', |a, b| a * b), op('/', |a, b| a / b));
+                let product = map(and(and(term, mul), factor.clone()), |((a, f), b)| f(a, b));
+                memo(or(product, factor))
+            });
+            let add = or(op('+', |a, b| a + b), op('-', |a, b| a - b));
+            let sum = map(and(and(expr, add), term.clone()), |((a, f), b)| f(a, b));
+            memo(or(sum, term))
+        });
+        assert_eq!(Ok((5, "")), parse(&expr, "8-2-3*2/3+1"));
+        assert_eq!(Ok((8, "")), parse(&expr, "2*(7-2-1)"));
+        assert_eq!(Ok((1, "-")), parse(&expr, "1-"));
+    }
+
+    #[test]
+    fn test_indirect_left_recursion() {
+        let grammar = |memo_b: bool| {
+            recursive(move |a| {
+                let b = map(and(a, character('z')), |(v, c): (String, char)| {
+                    v + &c.to_string()
+                });
+                let b = if memo_b { memo(b) } else { b };
+                let bx = map(and(b, character('x')), |(v, c)| v + &c.to_string());
+                memo(or(bx, map(character('y'), String::from)))
+            })
+        };
+        assert_eq!(
+            Ok(("yzxzx".to_string(), "")),
+            parse(&grammar(false), "yzxzx")
+        );
+        // Both rules of the cycle memoized: `b` isn't regrown.
+        assert_eq!(
+            Ok(("y".to_string(), "zxzx")),
+            parse(&grammar(true), "yzxzx")
+        );
+    }
+
+    #[test]
+    fn test_recognize() {
+        let number = and(optional(character('-')), many1(skip(digit())));
+        assert_eq!(Ok(("-12", "x")), parse(&recognize(number.clone()), "-12x"));
+        let ((matched, (sign, _)), _) = parse(&consumed(number), "12").unwrap();
+        assert_eq!(("12", None), (matched, sign));
+        assert_eq!(Ok(("null", "!")), parse(&tag("null"), "null!"));
+    }
+
+    #[test]
+    fn test_take_while() {
+        let ident = take_while1(|c| c.is_alphanumeric() || *c == '_');
+        assert_eq!(Ok(("héllo_1", " x")), parse(&ident, "héllo_1 x"));
+        assert!(parse(&ident, " x").is_err());
+        assert_eq!(Ok(("ab", "\n")), parse(&take_till(|c| *c == '\n'), "ab\n"));
+        let hex = take_while_m_n(2, 4, |c| c.is_ascii_hexdigit());
+        assert_eq!(Ok(("fF0a", "1")), parse(&hex, "fF0a1"));
+        let comment = and(tag("/*"), take_until("*/"));
+        assert_eq!(Ok((("/*", " a "), "*/")), parse(&comment, "/* a */"));
+        assert_eq!(6, parse(&comment, "/* a *").unwrap_err().offset());
+    }
+
+    #[test]
+    fn test_char_set() {
+        let ident = CharSet::new()
+            .char('_')
+            .range('0'..='9')
+            .category(Category::Alphabetic)
+            .make();
+        assert_eq!(Ok((vec!['ж', '_', '1'], " ")), parse(&many1(ident), "ж_1 "));
+        assert_eq!(Ok(('t', "")), parse(&one_of("\"\\/bfnrt"), "t"));
+        assert!(parse(&none_of("\"\\"), "\\").is_err());
+    }
+
+    #[test]
+    fn test_character_range() {
+        let below = character_range('a'..'\u{e000}');
+        assert_eq!(Ok(('\u{d7ff}', "")), parse(&below, "\u{d7ff}"));
+        assert!(parse(&below, "\u{e000}").is_err());
+        let nothing = character_range(..'\0');
+        assert!(parse(&nothing, "\0").is_err());
+        let err = parse(&character_range('a'..='f'), "g").unwrap_err();
+        assert_eq!(&[Expected::Range('a', 'f')], err.expected());
+    }
+
+    #[test]
+    fn test_unicode() {
+        assert_eq!(Ok(("Grüße_2", "=")), parse(&identifier(), "Grüße_2="));
+        assert_eq!(Ok(("_имя", "")), parse(&identifier(), "_имя"));
+        assert!(parse(&identifier(), "2x").is_err());
+        assert_eq!(Ok(('λ', "")), parse(&unicode_letter(), "λ"));
+        let currency = unicode_category(GeneralCategory::CurrencySymbol);
+        assert_eq!(Ok(('€', "5")), parse(&currency, "€5"));
+    }
+
+    #[test]
+    fn test_chain() {
+        let number = || map(digit(), i64::from);
+        let minus = || map(character('-'), |_| |a: i64, b: i64| a - b);
+        assert_eq!(Ok((-4, "")), parse(&chain_left(number(), minus()), "1-2-3"));
+        assert_eq!(
+            Ok((2, "-")),
+            parse(&chain_right(number(), minus()), "1-2-3-")
+        );
+    }
+}
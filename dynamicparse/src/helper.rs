@@ -1,54 +1,198 @@
 use crate::combinator::*;
-use crate::Parser;
+use crate::unicode::{is_xid_continue, GeneralCategory};
+use crate::{Category, CharSet, Expected, ParseError, Parser};
 use std::ops::RangeBounds;
 use std::rc::Rc;
 
 pub struct CharParser;
 impl CharParser {
     pub fn make<'a>() -> Parser<'a, char> {
-        Rc::new(|s| {
-            let mut chars = s.chars();
-            match chars.next() {
-                Some(c) => Some((c, chars.as_str())),
-                _ => None,
-            }
+        Rc::new(|s| match s.rest().chars().next() {
+            Some(c) => Ok((c, s.advance(c.len_utf8()))),
+            _ => Err(s.exhausted(1, vec![Expected::Any])),
         })
     }
 }
 pub fn character<'a>(c: char) -> Parser<'a, char> {
-    filter(CharParser::make(), move |v| *v == c)
+    expect(
+        filter(CharParser::make(), move |v| *v == c),
+        Expected::Char(c),
+    )
 }
 pub fn character_range<'a, R>(r: R) -> Parser<'a, char>
 where
     R: RangeBounds<char> + 'a,
 {
-    filter(CharParser::make(), move |parsed| r.contains(parsed))
+    CharSet::new().range(r).make()
 }
 pub fn digit<'a>() -> Parser<'a, u8> {
-    map(
-        filter(CharParser::make(), |parsed| parsed.is_ascii_digit()),
-        |c| c.to_digit(10).unwrap() as u8,
+    expect(
+        map(
+            filter(CharParser::make(), |parsed| parsed.is_ascii_digit()),
+            |c| c.to_digit(10).unwrap() as u8,
+        ),
+        Expected::Label("digit"),
     )
 }
+/// A letter in any script.
+pub fn unicode_letter<'a>() -> Parser<'a, char> {
+    CharSet::new().category(Category::Letter).make()
+}
+pub fn unicode_alphanumeric<'a>() -> Parser<'a, char> {
+    CharSet::new().category(Category::Alphanumeric).make()
+}
+pub fn unicode_whitespace<'a>() -> Parser<'a, char> {
+    CharSet::new().category(Category::Whitespace).make()
+}
+/// A character of the given general category.
+pub fn unicode_category<'a>(category: GeneralCategory) -> Parser<'a, char> {
+    CharSet::new().category(Category::General(category)).make()
+}
+/// An identifier per UAX #31, XID_Start then XID_Continue characters, where
+/// a leading underscore is also allowed.
+pub fn identifier<'a>() -> Parser<'a, &'a str> {
+    let start = CharSet::new().char('_').category(Category::XidStart).make();
+    let rest = take_while(|c| is_xid_continue(*c));
+    label(recognize(and(start, rest)), "identifier")
+}
+pub fn end<'a>() -> Parser<'a, ()> {
+    Rc::new(|s| {
+        if !s.rest().is_empty() {
+            Err(ParseError::new(s.offset(), vec![Expected::End]))
+        } else if s.is_partial() {
+            Err(ParseError::incomplete(s.offset(), 1))
+        } else {
+            Ok(((), s))
+        }
+    })
+}
+
+/// Counts up to `max` chars at the start of `s` for which `pred` holds,
+/// returning the count and the bytes they take. ASCII bytes are tested as
+/// they are; only the rest get decoded.
+fn scan(s: &str, max: usize, pred: impl Fn(&char) -> bool) -> (usize, usize) {
+    let bytes = s.as_bytes();
+    let (mut count, mut len) = (0, 0);
+    while count < max && len < bytes.len() {
+        let c = match bytes[len] {
+            b if b.is_ascii() => b as char,
+            _ => s[len..].chars().next().unwrap(),
+        };
+        if !pred(&c) {
+            break;
+        }
+        len += c.len_utf8();
+        count += 1;
+    }
+    (count, len)
+}
+
+/// Between `m` and `n` chars satisfying `pred`, taking as many as it can, as
+/// a slice of the input.
+pub fn take_while_m_n<'a>(
+    m: usize,
+    n: usize,
+    pred: impl Fn(&char) -> bool + 'a,
+) -> Parser<'a, &'a str> {
+    Rc::new(move |s| {
+        let rest = s.rest();
+        let (count, len) = scan(rest, n, &pred);
+        let r = s.advance(len);
+        // Out of input with room for more: the run may not be over yet.
+        if count < n && r.rest().is_empty() && r.is_partial() {
+            return Err(ParseError::incomplete(
+                r.offset(),
+                m.saturating_sub(count).max(1),
+            ));
+        }
+        if count < m {
+            return Err(ParseError::unexpected(r.offset()));
+        }
+        Ok((&rest[..len], r))
+    })
+}
+
+/// The longest run of chars satisfying `pred`, possibly empty.
+pub fn take_while<'a>(pred: impl Fn(&char) -> bool + 'a) -> Parser<'a, &'a str> {
+    take_while_m_n(0, usize::MAX, pred)
+}
+
+/// Like `take_while`, but fails unless at least one char matches.
+pub fn take_while1<'a>(pred: impl Fn(&char) -> bool + 'a) -> Parser<'a, &'a str> {
+    take_while_m_n(1, usize::MAX, pred)
+}
+
+/// The longest run of chars up to the first one satisfying `pred`.
+pub fn take_till<'a>(pred: impl Fn(&char) -> bool + 'a) -> Parser<'a, &'a str> {
+    take_while(move |c| !pred(c))
+}
+
+/// Everything before the next occurrence of `needle`, which is left for the
+/// following parser; fails if `needle` never shows up.
+pub fn take_until<'a>(needle: &'a str) -> Parser<'a, &'a str> {
+    Rc::new(move |s| match s.rest().find(needle) {
+        Some(at) => Ok((&s.rest()[..at], s.advance(at))),
+        None => {
+            let len = s.rest().len();
+            let end = s.advance(len);
+            Err(end.exhausted(1, vec![Expected::Token(needle.to_owned())]))
+        }
+    })
+}
+
+/// Any one of `chars`, looked up in a table rather than tried in turn.
+pub fn one_of<'a>(chars: &str) -> Parser<'a, char> {
+    CharSet::from(chars).make()
+}
+
+/// Any character but those in `chars`.
+pub fn none_of<'a>(chars: &str) -> Parser<'a, char> {
+    CharSet::from(chars).negate().make()
+}
 
 pub struct Token {
     token: String,
 }
 impl<'a> Token {
-    pub fn new(self) -> Parser<'a, String> {
+    pub fn make(self) -> Parser<'a, String> {
         Rc::new(move |s| {
-            if let Some(r) = s.strip_prefix(self.token.as_str()) {
-                let v = s[..self.token.len()].to_owned();
-                Some((v, r))
+            let expected = vec![Expected::Token(self.token.clone())];
+            if s.rest().starts_with(self.token.as_str()) {
+                Ok((self.token.clone(), s.advance(self.token.len())))
+            } else if self.token.starts_with(s.rest()) {
+                Err(s.exhausted(self.token.len() - s.rest().len(), expected))
             } else {
-                None
+                Err(ParseError::new(s.offset(), expected))
             }
         })
     }
+
+    #[deprecated(note = "renamed to `make`, like the other parser builders")]
+    #[allow(clippy::new_ret_no_self, clippy::wrong_self_convention)]
+    pub fn new(self) -> Parser<'a, String> {
+        self.make()
+    }
 }
-pub fn token(s: &str) -> Parser<String> {
+/// Matches `s` and returns it as an owned `String`; `tag(s)` is the
+/// borrowed variant.
+pub fn token<'a>(s: &str) -> Parser<'a, String> {
     Token {
         token: s.to_owned(),
     }
-    .new()
+    .make()
+}
+
+/// Matches `t`, returning the matched slice of the input.
+pub fn tag<'a>(t: &'a str) -> Parser<'a, &'a str> {
+    Rc::new(move |s| {
+        if s.rest().starts_with(t) {
+            return Ok((&s.rest()[..t.len()], s.advance(t.len())));
+        }
+        let expected = vec![Expected::Token(t.to_owned())];
+        if t.starts_with(s.rest()) {
+            Err(s.exhausted(t.len() - s.rest().len(), expected))
+        } else {
+            Err(ParseError::new(s.offset(), expected))
+        }
+    })
 }
@@ -1,6 +1,45 @@
 use std::rc::Rc;
 
+mod charset;
 pub mod combinator;
+mod error;
 pub mod helper;
+pub mod number;
+pub mod pratt;
+mod state;
 
-pub type Parser<'a, T> = Rc<dyn Fn(&'a str) -> Option<(T, &'a str)> + 'a>;
+pub use crate::charset::CharSet;
+pub use crate::error::{Expected, ParseError};
+pub use crate::state::{Spanned, State};
+pub use parsecore::{line_column, unicode, Category};
+
+pub type ParseResult<'a, T> = Result<(T, State<'a>), ParseError>;
+
+pub type Parser<'a, T> = Rc<dyn Fn(State<'a>) -> ParseResult<'a, T> + 'a>;
+
+/// Outcome of `parse_partial`: either a value and the unparsed rest, or how
+/// many more bytes are needed before the parser can decide.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status<'a, T> {
+    Done(T, &'a str),
+    Incomplete { needed: usize },
+}
+
+pub fn parse<'a, T>(parser: &Parser<'a, T>, s: &'a str) -> Result<(T, &'a str), ParseError> {
+    parser(State::new(s)).map(|(v, r)| (v, r.rest()))
+}
+
+/// Like `parse`, but `s` is only the beginning of the input: running off its
+/// end gives `Status::Incomplete` so the caller can append more and retry.
+pub fn parse_partial<'a, T>(
+    parser: &Parser<'a, T>,
+    s: &'a str,
+) -> Result<Status<'a, T>, ParseError> {
+    match parser(State::partial(s)) {
+        Ok((v, r)) => Ok(Status::Done(v, r.rest())),
+        Err(e) => match e.needed() {
+            Some(needed) => Ok(Status::Incomplete { needed }),
+            None => Err(e),
+        },
+    }
+}
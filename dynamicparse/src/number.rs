@@ -0,0 +1,174 @@
+use crate::{Expected, ParseError, Parser};
+use parsecore::number::{FloatFormat, IntegerFormat, ScanError};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+pub use parsecore::number::{Float, Integer};
+
+impl From<ScanError> for ParseError {
+    fn from(e: ScanError) -> Self {
+        match e {
+            ScanError::Incomplete(at) => ParseError::incomplete(at, 1),
+            ScanError::Expected(at, label) => ParseError::new(at, vec![Expected::Label(label)]),
+        }
+    }
+}
+
+/// Builds a parser for the digits of an integer in some radix, which fails
+/// rather than wraps when the number doesn't fit in `T`.
+#[derive(Clone)]
+pub struct IntegerParser<T> {
+    format: IntegerFormat,
+    _marker: PhantomData<T>,
+}
+
+impl<T> IntegerParser<T> {
+    /// Digits in `radix`, from 2 to 36, without a sign.
+    pub fn new(radix: u32) -> Self {
+        IntegerParser {
+            format: IntegerFormat::new(radix),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Accepts a leading `+` or `-`.
+    pub fn signed(mut self) -> Self {
+        self.format = self.format.signed();
+        self
+    }
+
+    /// Accepts single underscores between digits, as in `1_000_000`.
+    pub fn underscores(mut self) -> Self {
+        self.format = self.format.underscores();
+        self
+    }
+
+    pub fn make<'a>(self) -> Parser<'a, T>
+    where
+        T: Integer + 'a,
+    {
+        Rc::new(move |s| {
+            let offset = s.offset();
+            let (value, end) = self.format.scan(s.source(), offset, s.is_partial())?;
+            Ok((value, s.advance(end - offset)))
+        })
+    }
+}
+
+/// A decimal integer with an optional sign.
+pub fn integer<'a, T: Integer + 'a>() -> Parser<'a, T> {
+    IntegerParser::new(10).signed().make()
+}
+
+/// A decimal integer without a sign.
+pub fn unsigned<'a, T: Integer + 'a>() -> Parser<'a, T> {
+    IntegerParser::new(10).make()
+}
+
+/// Hexadecimal digits, either case, without a `0x` prefix.
+pub fn hex_integer<'a, T: Integer + 'a>() -> Parser<'a, T> {
+    IntegerParser::new(16).make()
+}
+
+pub fn octal<'a, T: Integer + 'a>() -> Parser<'a, T> {
+    IntegerParser::new(8).make()
+}
+
+pub fn binary<'a, T: Integer + 'a>() -> Parser<'a, T> {
+    IntegerParser::new(2).make()
+}
+
+/// Builds a floating-point parser that converts what it matched with correct
+/// rounding. By default it takes the decimal syntax `-12.5e-3`, `.5` and `1.`
+/// included; the builder methods enable more.
+pub struct FloatParser<T> {
+    format: FloatFormat,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Default for FloatParser<T> {
+    fn default() -> Self {
+        FloatParser {
+            format: FloatFormat::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> FloatParser<T> {
+    pub fn new() -> Self {
+        FloatParser::default()
+    }
+
+    /// Also accepts `inf`, `infinity` and `nan`, in any case.
+    pub fn special(mut self) -> Self {
+        self.format = self.format.special();
+        self
+    }
+
+    /// Also accepts a leading `+`.
+    pub fn plus(mut self) -> Self {
+        self.format = self.format.plus();
+        self
+    }
+
+    /// Also accepts hexadecimal floats such as `0x1.8p3`, where the binary
+    /// exponent is required.
+    pub fn hex(mut self) -> Self {
+        self.format = self.format.hex();
+        self
+    }
+
+    pub fn make<'a>(self) -> Parser<'a, T>
+    where
+        T: Float + 'a,
+    {
+        Rc::new(move |s| {
+            let offset = s.offset();
+            let (value, end) = self.format.scan(s.source(), offset, s.is_partial())?;
+            Ok((value, s.advance(end - offset)))
+        })
+    }
+}
+
+/// A decimal floating-point number, see `FloatParser` for the options.
+pub fn float<'a, T: Float + 'a>() -> Parser<'a, T> {
+    FloatParser::new().make()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinator::and;
+    use crate::helper::tag;
+    use crate::parse;
+
+    #[test]
+    fn test_parse_integers() {
+        assert_eq!(Ok((-42_i64, "x")), parse(&integer(), "-42x"));
+        assert_eq!(Ok((0xff_u8, "g")), parse(&hex_integer(), "fFg"));
+        let bits = and(tag("0b"), IntegerParser::<u8>::new(2).underscores().make());
+        assert_eq!(Ok((("0b", 0b1010_0101), "_")), parse(&bits, "0b1010_0101_"));
+        let err = parse(&integer::<i32>(), "-2147483649").unwrap_err();
+        assert_eq!(&[Expected::Label("integer in i32 range")], err.expected());
+        assert_eq!(Ok((i32::MIN, "")), parse(&integer(), "-2147483648"));
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(Ok((1.05_f64, "")), parse(&float(), "1.05"));
+        assert_eq!(Ok((-2.5e-3_f64, ",")), parse(&float(), "-2.5e-3,"));
+        assert_eq!(Ok((1_f32, "e")), parse(&float(), "1.e"));
+        assert!(parse(&float::<f64>(), "+1").is_err());
+        let special = FloatParser::<f64>::new().special().plus().make();
+        assert_eq!(Ok((f64::INFINITY, "")), parse(&special, "+inf"));
+        let hex = FloatParser::<f64>::new().hex().make();
+        assert_eq!(Ok((12.0, "")), parse(&hex, "0x1.8p3"));
+        assert_eq!(Ok((f64::from_bits(2), "")), parse(&hex, "0x1.8p-1074"));
+        let err = parse(&float::<f64>(), "-x").unwrap_err();
+        assert_eq!(
+            (1, &[Expected::Label("number")][..]),
+            (err.offset(), err.expected())
+        );
+    }
+}
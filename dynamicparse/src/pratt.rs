@@ -0,0 +1,188 @@
+use crate::combinator::map;
+use crate::{ParseError, ParseResult, Parser, State};
+use std::rc::Rc;
+
+/// How a chain of infix operators of the same power groups: `a - b - c` is
+/// `(a - b) - c` for `Left`, `a ^ b ^ c` is `a ^ (b ^ c)` for `Right`, and
+/// `a == b == c` is an error for `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+struct Unary<'a, T> {
+    op: Parser<'a, ()>,
+    power: u32,
+    fold: Rc<dyn Fn(T) -> T + 'a>,
+}
+
+struct Binary<'a, T> {
+    op: Parser<'a, ()>,
+    assoc: Assoc,
+    power: u32,
+    fold: Rc<dyn Fn(T, T) -> T + 'a>,
+}
+
+/// Builds an operator-precedence expression parser: operands come from the
+/// atom, operators are registered with a power, higher binding tighter, and
+/// a function folding their operands. `build` turns it into a `Parser`.
+pub struct Pratt<'a, T> {
+    atom: Parser<'a, T>,
+    prefix: Vec<Unary<'a, T>>,
+    infix: Vec<Binary<'a, T>>,
+    postfix: Vec<Unary<'a, T>>,
+}
+
+impl<'a, T: 'a> Pratt<'a, T> {
+    pub fn new(atom: Parser<'a, T>) -> Self {
+        Pratt {
+            atom,
+            prefix: Vec::new(),
+            infix: Vec::new(),
+            postfix: Vec::new(),
+        }
+    }
+
+    pub fn prefix<O: 'a>(
+        mut self,
+        power: u32,
+        op: Parser<'a, O>,
+        fold: impl Fn(T) -> T + 'a,
+    ) -> Self {
+        self.prefix.push(Unary {
+            op: map(op, |_| ()),
+            power,
+            fold: Rc::new(fold),
+        });
+        self
+    }
+
+    pub fn infix<O: 'a>(
+        mut self,
+        assoc: Assoc,
+        power: u32,
+        op: Parser<'a, O>,
+        fold: impl Fn(T, T) -> T + 'a,
+    ) -> Self {
+        self.infix.push(Binary {
+            op: map(op, |_| ()),
+            assoc,
+            power,
+            fold: Rc::new(fold),
+        });
+        self
+    }
+
+    pub fn postfix<O: 'a>(
+        mut self,
+        power: u32,
+        op: Parser<'a, O>,
+        fold: impl Fn(T) -> T + 'a,
+    ) -> Self {
+        self.postfix.push(Unary {
+            op: map(op, |_| ()),
+            power,
+            fold: Rc::new(fold),
+        });
+        self
+    }
+
+    pub fn build(self) -> Parser<'a, T> {
+        Rc::new(move |s| self.expression(s, 0))
+    }
+
+    /// An atom, or a prefix operator applied to an operand.
+    fn operand(&self, s: State<'a>) -> ParseResult<'a, T> {
+        let mut error: Option<ParseError> = None;
+        for prefix in &self.prefix {
+            match (prefix.op)(s.clone()) {
+                Ok(((), r)) => {
+                    let (v, r) = self.expression(r, 2 * prefix.power)?;
+                    return Ok(((prefix.fold)(v), r));
+                }
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) => error = Some(merge(error, e)),
+            }
+        }
+        (self.atom)(s).map_err(|e| merge(error, e))
+    }
+
+    /// An expression whose operators all bind at least as tightly as `min`.
+    fn expression(&self, s: State<'a>, min: u32) -> ParseResult<'a, T> {
+        let (mut lhs, mut s) = self.operand(s)?;
+        let mut chained: Option<u32> = None;
+        'operators: loop {
+            for postfix in self.postfix.iter().filter(|op| 2 * op.power >= min) {
+                match (postfix.op)(s.clone()) {
+                    Ok(((), r)) => {
+                        lhs = (postfix.fold)(lhs);
+                        s = r;
+                        continue 'operators;
+                    }
+                    Err(e) => s = s.backtrack(e)?,
+                }
+            }
+            for infix in &self.infix {
+                let (left, right) = match infix.assoc {
+                    Assoc::Left | Assoc::None => (2 * infix.power, 2 * infix.power + 1),
+                    Assoc::Right => (2 * infix.power + 1, 2 * infix.power),
+                };
+                if left < min {
+                    continue;
+                }
+                match (infix.op)(s.clone()) {
+                    Ok(((), r)) => {
+                        if infix.assoc == Assoc::None && chained == Some(infix.power) {
+                            return Err(ParseError::unexpected(s.offset()));
+                        }
+                        let (rhs, r) = self.expression(r, right)?;
+                        lhs = (infix.fold)(lhs, rhs);
+                        s = r;
+                        chained = (infix.assoc == Assoc::None).then_some(infix.power);
+                        continue 'operators;
+                    }
+                    Err(e) => s = s.backtrack(e)?,
+                }
+            }
+            return Ok((lhs, s));
+        }
+    }
+}
+
+fn merge(error: Option<ParseError>, e: ParseError) -> ParseError {
+    match error {
+        Some(error) => error.merge(e),
+        None => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinator::*;
+    use crate::helper::*;
+    use crate::parse;
+
+    #[test]
+    fn test_pratt() {
+        let calc = recursive(|expr| {
+            let number = map(many1(digit()), |ds| {
+                ds.iter().fold(0, |n, &d| n * 10 + d as i64)
+            });
+            let parens = between(expr, character('('), character(')'));
+            Pratt::new(or(number, parens))
+                .infix(Assoc::Left, 1, character('-'), |a, b| a - b)
+                .infix(Assoc::Left, 2, character('*'), |a, b| a * b)
+                .prefix(3, character('-'), |a| -a)
+                .infix(Assoc::Right, 4, character('^'), |a, b| a.pow(b as u32))
+                .postfix(5, character('!'), |a| (1..=a).product())
+                .build()
+        });
+        assert_eq!(Ok((3, "")), parse(&calc, "10-4-3"));
+        assert_eq!(Ok((512, "")), parse(&calc, "2^3^2"));
+        assert_eq!(Ok((-4, "")), parse(&calc, "-2^2"));
+        assert_eq!(Ok((17, "")), parse(&calc, "(7-4)!*3-1"));
+    }
+}
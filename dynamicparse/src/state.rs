@@ -0,0 +1,156 @@
+use crate::{line_column, Expected, ParseError};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// A position in the original input.
+///
+/// Parsers receive the whole source together with the byte offset they should
+/// start at, so they can report where a value or an error came from instead of
+/// only seeing the remaining suffix.
+///
+/// A partial state is the beginning of an input that's still arriving, so
+/// running off its end means "need more" rather than "failed".
+///
+/// Every new state starts a fresh parse run with its own id, so memoizing
+/// parsers can tell results for this input apart from those of earlier runs.
+///
+/// The hint a state may carry is the error of an alternative given up on right
+/// here, such as the element that ended a `many`. Consuming input drops it,
+/// and a failure before that has it merged in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State<'a> {
+    src: &'a str,
+    offset: usize,
+    partial: bool,
+    run: usize,
+    hint: Option<Rc<ParseError>>,
+}
+
+impl<'a> State<'a> {
+    pub fn new(src: &'a str) -> Self {
+        State {
+            src,
+            offset: 0,
+            partial: false,
+            run: RUNS.fetch_add(1, Ordering::Relaxed),
+            hint: None,
+        }
+    }
+
+    pub fn partial(src: &'a str) -> Self {
+        State {
+            partial: true,
+            ..State::new(src)
+        }
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    /// The error for running out of input here: incomplete when more input may
+    /// still arrive, a plain failure otherwise.
+    pub fn exhausted(&self, needed: usize, expected: Vec<Expected>) -> ParseError {
+        if self.partial {
+            ParseError::incomplete(self.offset, needed)
+        } else {
+            ParseError::new(self.offset, expected)
+        }
+    }
+
+    pub(crate) fn run(&self) -> usize {
+        self.run
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.src
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.src[self.offset..]
+    }
+
+    pub fn advance(self, n: usize) -> Self {
+        if n == 0 {
+            return self;
+        }
+        State {
+            offset: self.offset + n,
+            hint: None,
+            ..self
+        }
+    }
+
+    /// `error` merged with this state's hint, for a parser that failed after
+    /// starting here.
+    pub fn fail(&self, error: ParseError) -> ParseError {
+        match &self.hint {
+            Some(hint) => (**hint).clone().merge(error),
+            None => error,
+        }
+    }
+
+    /// Backtracks out of a parser that failed with `error` after starting
+    /// here, keeping `error` as a hint; fatal errors are handed back instead.
+    pub fn backtrack(self, error: ParseError) -> Result<Self, ParseError> {
+        let error = self.fail(error);
+        if error.is_fatal() {
+            return Err(error);
+        }
+        Ok(State {
+            hint: Some(Rc::new(error)),
+            ..self
+        })
+    }
+
+    /// Renames what this state's hint expects, if it's for `offset`.
+    pub(crate) fn relabel(mut self, offset: usize, expected: &Expected) -> Self {
+        if let Some(hint) = self.hint.take() {
+            let hint = if hint.offset() == offset {
+                Rc::new((*hint).clone().expecting(expected.clone()))
+            } else {
+                hint
+            };
+            self.hint = Some(hint);
+        }
+        self
+    }
+
+    /// Marks this state as the end of the rule `name`: a failure here is
+    /// reported as coming after it, in place of whatever the rule itself still
+    /// expected.
+    pub(crate) fn after(mut self, name: &'static str) -> Self {
+        if self.hint.as_ref().is_none_or(|h| h.offset() == self.offset) {
+            let marker = ParseError::new(self.offset, Vec::new()).following(name);
+            self.hint = Some(Rc::new(marker));
+        }
+        self
+    }
+
+    pub(crate) fn hint(&self) -> Option<Rc<ParseError>> {
+        self.hint.clone()
+    }
+
+    pub(crate) fn with_hint(self, hint: Option<Rc<ParseError>>) -> Self {
+        State { hint, ..self }
+    }
+
+    pub fn line_column(&self) -> (usize, usize) {
+        line_column(self.src, self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
@@ -0,0 +1,233 @@
+#!/usr/bin/env perl
+# Generates src/unicode.rs from the Unicode character database bundled with
+# Perl, so every property in it comes from the same Unicode version:
+#
+#     perl gen_unicode.pl > src/unicode.rs
+#
+# Run it from this directory. The version printed in the module docs is
+# whatever Unicode::UCD ships with the perl that ran it.
+
+use strict;
+use warnings;
+use Unicode::UCD qw(prop_invlist prop_invmap);
+
+my @categories = (
+    [Lu => "UppercaseLetter", "uppercase letter"],
+    [Ll => "LowercaseLetter", "lowercase letter"],
+    [Lt => "TitlecaseLetter", "titlecase letter"],
+    [Lm => "ModifierLetter", "modifier letter"],
+    [Lo => "OtherLetter", "other letter"],
+    [Mn => "NonspacingMark", "nonspacing mark"],
+    [Mc => "SpacingMark", "spacing mark"],
+    [Me => "EnclosingMark", "enclosing mark"],
+    [Nd => "DecimalNumber", "decimal number"],
+    [Nl => "LetterNumber", "letter number"],
+    [No => "OtherNumber", "other number"],
+    [Pc => "ConnectorPunctuation", "connector punctuation"],
+    [Pd => "DashPunctuation", "dash punctuation"],
+    [Ps => "OpenPunctuation", "open punctuation"],
+    [Pe => "ClosePunctuation", "close punctuation"],
+    [Pi => "InitialPunctuation", "initial punctuation"],
+    [Pf => "FinalPunctuation", "final punctuation"],
+    [Po => "OtherPunctuation", "other punctuation"],
+    [Sm => "MathSymbol", "math symbol"],
+    [Sc => "CurrencySymbol", "currency symbol"],
+    [Sk => "ModifierSymbol", "modifier symbol"],
+    [So => "OtherSymbol", "other symbol"],
+    [Zs => "SpaceSeparator", "space separator"],
+    [Zl => "LineSeparator", "line separator"],
+    [Zp => "ParagraphSeparator", "paragraph separator"],
+    [Cc => "Control", "control character"],
+    [Cf => "Format", "format character"],
+    [Cs => "Surrogate", "surrogate"],
+    [Co => "PrivateUse", "private use character"],
+    [Cn => "Unassigned", "unassigned character"],
+);
+my %index = map { $categories[$_][0] => $_ } 0 .. $#categories;
+
+# An inversion list as (first, last) pairs.
+sub ranges {
+    my @list = prop_invlist(shift);
+    push @list, 0x110000 if @list % 2;
+    my @ranges;
+    for (my $i = 0; $i < @list; $i += 2) {
+        push @ranges, sprintf("(0x%x, 0x%x),", $list[$i], $list[$i + 1] - 1);
+    }
+    return @ranges;
+}
+
+# Unassigned code points are left out; lookups default to them.
+sub general_categories {
+    my ($list, $map) = prop_invmap("General_Category");
+    my @rows;
+    for my $i (0 .. $#$list) {
+        next if $map->[$i] eq "Cn";
+        my $last = ($i < $#$list ? $list->[$i + 1] : 0x110000) - 1;
+        push @rows, sprintf("(0x%x, 0x%x, %d),", $list->[$i], $last, $index{$map->[$i]});
+    }
+    return @rows;
+}
+
+sub table {
+    my ($name, $type, $per_line, @rows) = @_;
+    my $out = "#[rustfmt::skip]\nconst $name: &[$type] = &[\n";
+    for (my $i = 0; $i < @rows; $i += $per_line) {
+        my $last = $i + $per_line - 1;
+        $last = $#rows if $last > $#rows;
+        $out .= "    " . join(" ", @rows[$i .. $last]) . "\n";
+    }
+    return $out . "];\n";
+}
+
+my $version = Unicode::UCD::UnicodeVersion();
+my $count = @categories;
+my $variants = join "", map { "    $_->[1],\n" } @categories;
+my $names = join "", map { "            GeneralCategory::$_->[1] => \"$_->[2]\",\n" } @categories;
+my $by_index = join "", map { "        $_->[1],\n" } @categories;
+my $tables = join "\n",
+    table("XID_START", "(u32, u32)", 6, ranges("XID_Start")),
+    table("XID_CONTINUE", "(u32, u32)", 6, ranges("XID_Continue")),
+    table("GENERAL_CATEGORY", "(u32, u32, u8)", 5, general_categories()),
+    table("ALPHABETIC", "(u32, u32)", 6, ranges("Alphabetic")),
+    table("LOWERCASE", "(u32, u32)", 6, ranges("Lowercase")),
+    table("UPPERCASE", "(u32, u32)", 6, ranges("Uppercase")),
+    table("WHITE_SPACE", "(u32, u32)", 6, ranges("White_Space"));
+chomp $tables;
+
+print <<"RUST";
+//! Unicode character properties, all taken from the Unicode $version
+//! character database so that they agree with each other: general
+//! categories, the XID_Start and XID_Continue properties UAX #31 defines
+//! identifiers with, and the properties behind `char::is_alphabetic` and its
+//! siblings. The standard library's methods follow whichever Unicode version
+//! it was built with, so characters added since $version would get a
+//! category from one version and a property from another.
+//!
+//! Generated by `gen_unicode.pl`; edit that instead.
+
+use std::cmp::Ordering;
+
+/// The version of Unicode the tables here come from.
+pub const UNICODE_VERSION: &str = "$version";
+
+/// A character's Unicode general category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneralCategory {
+$variants}
+
+impl GeneralCategory {
+    /// Whether this is one of the letter categories, `L`.
+    pub fn is_letter(self) -> bool {
+        (self as u8) <= GeneralCategory::OtherLetter as u8
+    }
+
+    /// Whether this is one of the number categories, `N`.
+    pub fn is_number(self) -> bool {
+        matches!(
+            self,
+            GeneralCategory::DecimalNumber
+                | GeneralCategory::LetterNumber
+                | GeneralCategory::OtherNumber
+        )
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+$names        }
+    }
+}
+
+/// The general category of `c`.
+pub fn general_category(c: char) -> GeneralCategory {
+    use GeneralCategory::*;
+    const BY_INDEX: [GeneralCategory; $count] = [
+$by_index    ];
+    match search(GENERAL_CATEGORY, c, |&(lo, hi, _)| (lo, hi)) {
+        Some(&(_, _, category)) => BY_INDEX[category as usize],
+        None => Unassigned,
+    }
+}
+
+/// Whether `c` can start an identifier.
+pub fn is_xid_start(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_alphabetic();
+    }
+    search(XID_START, c, |&range| range).is_some()
+}
+
+/// Whether `c` can appear in an identifier after its first character.
+pub fn is_xid_continue(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_alphanumeric() || c == '_';
+    }
+    search(XID_CONTINUE, c, |&range| range).is_some()
+}
+
+/// Whether `c` has the Alphabetic property, like `char::is_alphabetic`.
+pub fn is_alphabetic(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_alphabetic();
+    }
+    search(ALPHABETIC, c, |&range| range).is_some()
+}
+
+/// Whether `c` is in one of the number categories, like `char::is_numeric`.
+pub fn is_numeric(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_digit();
+    }
+    general_category(c).is_number()
+}
+
+/// Whether `c` is alphabetic or numeric, like `char::is_alphanumeric`.
+pub fn is_alphanumeric(c: char) -> bool {
+    is_alphabetic(c) || is_numeric(c)
+}
+
+/// Whether `c` has the Lowercase property, like `char::is_lowercase`.
+pub fn is_lowercase(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_lowercase();
+    }
+    search(LOWERCASE, c, |&range| range).is_some()
+}
+
+/// Whether `c` has the Uppercase property, like `char::is_uppercase`.
+pub fn is_uppercase(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_uppercase();
+    }
+    search(UPPERCASE, c, |&range| range).is_some()
+}
+
+/// Whether `c` has the White_Space property, like `char::is_whitespace`.
+pub fn is_whitespace(c: char) -> bool {
+    search(WHITE_SPACE, c, |&range| range).is_some()
+}
+
+/// Whether `c` is in the control category `Cc`, like `char::is_control`.
+pub fn is_control(c: char) -> bool {
+    general_category(c) == GeneralCategory::Control
+}
+
+/// The entry of a sorted table of disjoint ranges that contains `c`.
+fn search<T>(table: &[T], c: char, range: impl Fn(&T) -> (u32, u32)) -> Option<&T> {
+    let c = c as u32;
+    table
+        .binary_search_by(|entry| {
+            let (lo, hi) = range(entry);
+            if hi < c {
+                Ordering::Less
+            } else if lo > c {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+        .ok()
+        .map(|i| &table[i])
+}
+
+$tables
+RUST
@@ -0,0 +1,282 @@
+use crate::unicode::{self, general_category, is_xid_continue, is_xid_start, GeneralCategory};
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+use std::rc::Rc;
+
+/// Unicode character classes a `CharSet` can include: the properties behind
+/// the standard library's `char` methods, general categories, and the
+/// identifier properties of UAX #31, all from the same Unicode version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Any letter, general category `L`.
+    Letter,
+    General(GeneralCategory),
+    XidStart,
+    XidContinue,
+    Alphabetic,
+    Alphanumeric,
+    Numeric,
+    Lowercase,
+    Uppercase,
+    Whitespace,
+    Control,
+}
+
+impl Category {
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Category::Letter => general_category(c).is_letter(),
+            Category::General(category) => general_category(c) == category,
+            Category::XidStart => is_xid_start(c),
+            Category::XidContinue => is_xid_continue(c),
+            Category::Alphabetic => unicode::is_alphabetic(c),
+            Category::Alphanumeric => unicode::is_alphanumeric(c),
+            Category::Numeric => unicode::is_numeric(c),
+            Category::Lowercase => unicode::is_lowercase(c),
+            Category::Uppercase => unicode::is_uppercase(c),
+            Category::Whitespace => unicode::is_whitespace(c),
+            Category::Control => unicode::is_control(c),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Letter => "letter",
+            Category::General(category) => category.name(),
+            Category::XidStart => "identifier start",
+            Category::XidContinue => "identifier character",
+            Category::Alphabetic => "alphabetic character",
+            Category::Alphanumeric => "alphanumeric character",
+            Category::Numeric => "numeric character",
+            Category::Lowercase => "lowercase character",
+            Category::Uppercase => "uppercase character",
+            Category::Whitespace => "whitespace",
+            Category::Control => "control character",
+        }
+    }
+}
+
+/// One of the pieces a `CharSet` was built from, which is what a parser over
+/// the set reports as expected when it fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Member {
+    Char(char),
+    Range(char, char),
+    Category(Category),
+}
+
+#[derive(Debug, Clone, Default)]
+struct Table {
+    /// Sorted, disjoint and non-adjacent ranges of non-ASCII characters.
+    ranges: Vec<(char, char)>,
+    categories: Vec<Category>,
+    members: Vec<Member>,
+}
+
+impl Table {
+    /// Sorts the ranges and merges those that overlap or touch.
+    fn normalize(&mut self) {
+        self.ranges.sort_unstable();
+        self.ranges.dedup_by(|next, prev| {
+            if next.0 as u32 <= prev.1 as u32 + 1 {
+                prev.1 = prev.1.max(next.1);
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
+
+/// A set of characters, built from single characters, ranges and
+/// `Category`s. Both libraries turn it into a parser of any one character in
+/// it.
+///
+/// Membership of an ASCII character is a single bit test; anything else is a
+/// binary search through the set's ranges followed by its categories.
+#[derive(Debug, Clone, Default)]
+pub struct CharSet {
+    ascii: u128,
+    negated: bool,
+    table: Rc<Table>,
+}
+
+impl CharSet {
+    pub fn new() -> Self {
+        CharSet::default()
+    }
+
+    pub fn char(self, c: char) -> Self {
+        self.insert(c, c, Member::Char(c))
+    }
+
+    pub fn chars(self, chars: &str) -> Self {
+        chars.chars().fold(self, CharSet::char)
+    }
+
+    /// Every character in `r`; a range with nothing in it adds nothing.
+    pub fn range<R: RangeBounds<char>>(self, r: R) -> Self {
+        match bounds(&r) {
+            Some((lo, hi)) => self.insert(lo, hi, Member::Range(lo, hi)),
+            None => self,
+        }
+    }
+
+    pub fn category(mut self, category: Category) -> Self {
+        for b in 0..128u8 {
+            if category.contains(b as char) {
+                self.ascii |= 1 << b;
+            }
+        }
+        let table = Rc::make_mut(&mut self.table);
+        table.categories.push(category);
+        table.members.push(Member::Category(category));
+        self
+    }
+
+    /// The complement: every character this set doesn't contain.
+    pub fn negate(mut self) -> Self {
+        self.negated = !self.negated;
+        self
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        let found = if c.is_ascii() {
+            self.ascii & (1 << c as u32) != 0
+        } else {
+            self.table
+                .ranges
+                .binary_search_by(|&(lo, hi)| {
+                    if hi < c {
+                        Ordering::Less
+                    } else if lo > c {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Equal
+                    }
+                })
+                .is_ok()
+                || self.table.categories.iter().any(|cat| cat.contains(c))
+        };
+        found != self.negated
+    }
+
+    /// Every character in either set, or `None` when one of them is negated.
+    pub fn union(&self, other: &CharSet) -> Option<CharSet> {
+        if self.negated || other.negated {
+            return None;
+        }
+        let mut union = self.clone();
+        union.ascii |= other.ascii;
+        let table = Rc::make_mut(&mut union.table);
+        table.ranges.extend_from_slice(&other.table.ranges);
+        table.normalize();
+        for &category in &other.table.categories {
+            if !table.categories.contains(&category) {
+                table.categories.push(category);
+            }
+        }
+        for &member in &other.table.members {
+            if !table.members.contains(&member) {
+                table.members.push(member);
+            }
+        }
+        Some(union)
+    }
+
+    /// What a failure to match reports; a negated set expects nothing in
+    /// particular.
+    pub fn expected(&self) -> &[Member] {
+        if self.negated {
+            &[]
+        } else {
+            &self.table.members
+        }
+    }
+
+    fn insert(mut self, lo: char, hi: char, member: Member) -> Self {
+        for c in lo..=hi.min('\x7f') {
+            self.ascii |= 1 << c as u32;
+        }
+        let table = Rc::make_mut(&mut self.table);
+        if hi > '\x7f' {
+            table.ranges.push((lo.max('\u{80}'), hi));
+            table.normalize();
+        }
+        table.members.push(member);
+        self
+    }
+}
+
+impl From<&str> for CharSet {
+    fn from(chars: &str) -> Self {
+        CharSet::new().chars(chars)
+    }
+}
+
+/// The first and last character in `r`, or `None` when it has none.
+fn bounds<R: RangeBounds<char>>(r: &R) -> Option<(char, char)> {
+    let lo = match r.start_bound() {
+        Bound::Included(&c) => c,
+        Bound::Excluded(&c) => char_after(c)?,
+        Bound::Unbounded => char::MIN,
+    };
+    let hi = match r.end_bound() {
+        Bound::Included(&c) => c,
+        Bound::Excluded(&c) => char_before(c)?,
+        Bound::Unbounded => char::MAX,
+    };
+    (lo <= hi).then_some((lo, hi))
+}
+
+/// The next character after `c`, stepping over the surrogates.
+fn char_after(c: char) -> Option<char> {
+    match c {
+        '\u{d7ff}' => Some('\u{e000}'),
+        c => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// The character before `c`, stepping over the surrogates.
+fn char_before(c: char) -> Option<char> {
+    match c {
+        '\u{e000}' => Some('\u{d7ff}'),
+        c => char::from_u32((c as u32).checked_sub(1)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(Some(('a', '\u{d7ff}')), bounds(&('a'..'\u{e000}')));
+        let above = (Bound::Excluded('\u{d7ff}'), Bound::Unbounded);
+        assert_eq!(Some(('\u{e000}', char::MAX)), bounds(&above));
+        assert_eq!(None, bounds(&(..'\0')));
+        assert_eq!(
+            None,
+            bounds(&(Bound::Excluded(char::MAX), Bound::Unbounded))
+        );
+        assert_eq!(None, bounds(&('b'..'b')));
+    }
+
+    #[test]
+    fn test_union() {
+        let letters = CharSet::new().range('a'..='f').category(Category::Numeric);
+        let union = letters.union(&CharSet::from("xé")).unwrap();
+        assert!(union.contains('c') && union.contains('é') && union.contains('٣'));
+        assert!(!union.contains('g'));
+        assert_eq!(
+            &[
+                Member::Range('a', 'f'),
+                Member::Category(Category::Numeric),
+                Member::Char('x'),
+                Member::Char('é')
+            ],
+            union.expected()
+        );
+        assert!(letters.negate().union(&union).is_none());
+    }
+}
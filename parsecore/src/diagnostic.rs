@@ -0,0 +1,154 @@
+use std::fmt;
+
+/// 1-based line and column (in characters) of the byte `offset` in `src`.
+pub fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let before = &src[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// A borrowed view of a parse error, with everything needed to describe it.
+///
+/// Both parser libraries have their own `ParseError` and `Expected` types;
+/// they hand one of these out to get the same wording and layout. `after`
+/// names the rule that was just completed where the failure happened, as in
+/// "expected ',' or '}' after object member".
+pub struct Diagnostic<'e, E> {
+    pub offset: usize,
+    pub expected: &'e [E],
+    pub context: &'e [&'static str],
+    pub after: Option<&'e str>,
+}
+
+impl<E: fmt::Display> Diagnostic<'_, E> {
+    fn breadcrumbs(&self) -> Option<String> {
+        if self.context.is_empty() {
+            return None;
+        }
+        let trail: Vec<String> = self.context.iter().map(|c| format!("in {}", c)).collect();
+        Some(trail.join(" > "))
+    }
+
+    fn expectation(&self) -> Option<String> {
+        let (last, init) = self.expected.split_last()?;
+        if init.is_empty() {
+            return Some(last.to_string());
+        }
+        let init: Vec<String> = init.iter().map(|e| e.to_string()).collect();
+        Some(format!("{} or {}", init.join(", "), last))
+    }
+
+    /// Describes the failure in terms of what was found at `offset` in `src`.
+    pub fn message(&self, src: &str) -> String {
+        let found = src[self.offset..]
+            .chars()
+            .next()
+            .map_or("end of input".to_owned(), |c| format!("{:?}", c));
+        match (self.expectation(), self.after) {
+            (Some(expected), Some(after)) => {
+                format!("expected {} after {}, found {}", expected, after, found)
+            }
+            (Some(expected), None) => format!("expected {}, found {}", expected, found),
+            (None, Some(after)) => format!("unexpected {} after {}", found, after),
+            (None, None) => format!("unexpected {}", found),
+        }
+    }
+
+    /// Renders the failure rustc-style: the message, the offending line of
+    /// `src` and a caret under the failing column.
+    pub fn render(&self, src: &str) -> String {
+        let (line, column) = line_column(src, self.offset);
+        let line_start = src[..self.offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[self.offset..]
+            .find('\n')
+            .map_or(src.len(), |i| self.offset + i);
+        let text = src[line_start..line_end].trim_end_matches('\r');
+        let pad: String = src[line_start..self.offset]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let gutter = " ".repeat(line.to_string().len());
+        let mut out = format!(
+            "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}^\n",
+            self.message(src),
+            gutter,
+            line,
+            column,
+            gutter,
+            line,
+            text,
+            gutter,
+            pad
+        );
+        if let Some(trail) = self.breadcrumbs() {
+            out.push_str(&format!("{} = note: {}\n", gutter, trail));
+        }
+        out
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Diagnostic<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expectation() {
+            Some(expected) => write!(f, "expected {}", expected)?,
+            None => write!(f, "unexpected input")?,
+        }
+        if let Some(after) = self.after {
+            write!(f, " after {}", after)?;
+        }
+        write!(f, " at offset {}", self.offset)?;
+        match self.breadcrumbs() {
+            Some(trail) => write!(f, " ({})", trail),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let src = "one\n\ttwo\r\nthree";
+        assert_eq!((2, 2), line_column(src, 5));
+        let d = Diagnostic {
+            offset: 6,
+            expected: &["'x'", "'y'", "'z'"],
+            context: &["list", "item"],
+            after: None,
+        };
+        assert_eq!("expected 'x', 'y' or 'z', found 'w'", d.message(src));
+        assert_eq!(
+            "expected 'x', 'y' or 'z' at offset 6 (in list > in item)",
+            d.to_string()
+        );
+        assert_eq!(
+            "error: expected 'x', 'y' or 'z', found 'w'\n --> 2:3\n  |\n2 | \ttwo\n  | \t ^\n  = note: in list > in item\n",
+            d.render(src)
+        );
+
+        let d = Diagnostic::<&str> {
+            offset: src.len(),
+            expected: &[],
+            context: &[],
+            after: None,
+        };
+        assert_eq!("unexpected end of input", d.message(src));
+        assert_eq!("unexpected input at offset 15", d.to_string());
+
+        let d = Diagnostic {
+            offset: 3,
+            expected: &["','", "']'"],
+            context: &[],
+            after: Some("item"),
+        };
+        assert_eq!(
+            "expected ',' or ']' after item, found '\\n'",
+            d.message(src)
+        );
+        assert_eq!("expected ',' or ']' after item at offset 3", d.to_string());
+    }
+}
@@ -0,0 +1,10 @@
+//! Pieces `staticparse` and `dynamicparse` have in common, kept here so the
+//! two libraries can't drift apart.
+
+mod charset;
+mod diagnostic;
+pub mod number;
+pub mod unicode;
+
+pub use crate::charset::{Category, CharSet, Member};
+pub use crate::diagnostic::{line_column, Diagnostic};
@@ -0,0 +1,478 @@
+use std::ops::Neg;
+use std::str::FromStr;
+
+/// Text numbers are scanned from, addressed by offset the way `str` is by
+/// byte.
+pub trait Source {
+    /// The character at `at` and its length, or `None` at the end.
+    fn char_at(&self, at: usize) -> Option<(char, usize)>;
+
+    /// Where the run of ASCII digits starting at `at` ends.
+    fn digits_end(&self, mut at: usize) -> usize {
+        while let Some((c, len)) = self.char_at(at) {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            at += len;
+        }
+        at
+    }
+
+    /// The text from `start` to `end`, parsed with `FromStr`.
+    fn parse_text<T: FromStr>(&self, start: usize, end: usize) -> Option<T> {
+        let mut text = String::new();
+        let mut at = start;
+        while at < end {
+            let (c, len) = self.char_at(at)?;
+            text.push(c);
+            at += len;
+        }
+        text.parse().ok()
+    }
+}
+
+impl Source for str {
+    fn char_at(&self, at: usize) -> Option<(char, usize)> {
+        let c = self[at..].chars().next()?;
+        Some((c, c.len_utf8()))
+    }
+
+    fn digits_end(&self, at: usize) -> usize {
+        at + self[at..].bytes().take_while(u8::is_ascii_digit).count()
+    }
+
+    fn parse_text<T: FromStr>(&self, start: usize, end: usize) -> Option<T> {
+        self[start..end].parse().ok()
+    }
+}
+
+/// Why a number couldn't be scanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanError {
+    /// The text ended at this offset while the number might have gone on.
+    Incomplete(usize),
+    /// This offset doesn't hold what the label names.
+    Expected(usize, &'static str),
+}
+
+/// The primitive integer types numbers can be parsed into.
+pub trait Integer: Copy {
+    const ZERO: Self;
+
+    /// What an out-of-range number is reported as expecting.
+    const RANGE: &'static str;
+
+    /// Appends a digit: `self * radix + digit`, or `- digit` while a negative
+    /// number is being accumulated. `None` on overflow.
+    fn push_digit(self, radix: u32, digit: u32, negative: bool) -> Option<Self>;
+}
+
+macro_rules! integers {
+    ($($t:ty)*) => {
+        $(
+            impl Integer for $t {
+                const ZERO: Self = 0;
+                const RANGE: &'static str = concat!("integer in ", stringify!($t), " range");
+
+                fn push_digit(self, radix: u32, digit: u32, negative: bool) -> Option<Self> {
+                    let shifted = self.checked_mul(radix as $t)?;
+                    if negative {
+                        shifted.checked_sub(digit as $t)
+                    } else {
+                        shifted.checked_add(digit as $t)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integers!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize);
+
+/// The syntax of an integer: its radix, and whether it takes a sign and
+/// underscores between digits. Both libraries' integer parsers scan with it.
+#[derive(Debug, Clone, Copy)]
+pub struct IntegerFormat {
+    radix: u32,
+    signed: bool,
+    underscores: bool,
+}
+
+impl IntegerFormat {
+    /// Digits in `radix`, from 2 to 36, without a sign.
+    pub fn new(radix: u32) -> Self {
+        assert!((2..=36).contains(&radix), "radix {} out of range", radix);
+        IntegerFormat {
+            radix,
+            signed: false,
+            underscores: false,
+        }
+    }
+
+    /// Accepts a leading `+` or `-`.
+    pub fn signed(mut self) -> Self {
+        self.signed = true;
+        self
+    }
+
+    /// Accepts single underscores between digits, as in `1_000_000`.
+    pub fn underscores(mut self) -> Self {
+        self.underscores = true;
+        self
+    }
+
+    /// Scans an integer at `start`, returning it and the offset it ends at,
+    /// or failing rather than wrapping when it doesn't fit in `T`. `partial`
+    /// says more text may follow the end of `src`.
+    pub fn scan<T: Integer, S: Source + ?Sized>(
+        &self,
+        src: &S,
+        start: usize,
+        partial: bool,
+    ) -> Result<(T, usize), ScanError> {
+        let mut at = start;
+        let mut negative = false;
+        if self.signed {
+            match src.char_at(at) {
+                Some(('-', len)) => (negative, at) = (true, at + len),
+                Some(('+', len)) => at += len,
+                _ => {}
+            }
+        }
+        let digits = at;
+        let mut value = T::ZERO;
+        loop {
+            let mut next = at;
+            if self.underscores && at > digits {
+                if let Some(('_', len)) = src.char_at(next) {
+                    next += len;
+                }
+            }
+            let Some((c, len)) = src.char_at(next) else {
+                if partial {
+                    return Err(ScanError::Incomplete(next));
+                }
+                break;
+            };
+            let Some(digit) = c.to_digit(self.radix) else {
+                break;
+            };
+            value = value
+                .push_digit(self.radix, digit, negative)
+                .ok_or(ScanError::Expected(start, T::RANGE))?;
+            at = next + len;
+        }
+        if at == digits {
+            return Err(ScanError::Expected(digits, self.digit_label()));
+        }
+        Ok((value, at))
+    }
+
+    fn digit_label(&self) -> &'static str {
+        match self.radix {
+            2 => "binary digit",
+            8 => "octal digit",
+            16 => "hex digit",
+            _ => "digit",
+        }
+    }
+}
+
+/// The primitive floating-point types numbers can be parsed into.
+pub trait Float: FromStr + Neg<Output = Self> + Copy {
+    /// Significand bits, counting the implicit leading one.
+    const PRECISION: u32;
+    const MIN_EXP: i32;
+    const MAX_EXP: i32;
+
+    fn from_bits64(bits: u64) -> Self;
+}
+
+impl Float for f32 {
+    const PRECISION: u32 = f32::MANTISSA_DIGITS;
+    const MIN_EXP: i32 = f32::MIN_EXP - 1;
+    const MAX_EXP: i32 = f32::MAX_EXP - 1;
+
+    fn from_bits64(bits: u64) -> Self {
+        f32::from_bits(bits as u32)
+    }
+}
+
+impl Float for f64 {
+    const PRECISION: u32 = f64::MANTISSA_DIGITS;
+    const MIN_EXP: i32 = f64::MIN_EXP - 1;
+    const MAX_EXP: i32 = f64::MAX_EXP - 1;
+
+    fn from_bits64(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+}
+
+/// `mantissa * 2^exp` rounded to the nearest `T`, ties to even; `sticky` says
+/// whether nonzero digits were dropped below the mantissa.
+fn from_binary<T: Float>(mantissa: u64, exp: i32, sticky: bool) -> T {
+    if mantissa == 0 {
+        return T::from_bits64(0);
+    }
+    let zeros = mantissa.leading_zeros();
+    let mantissa = mantissa << zeros;
+    // The value is 1.xxx * 2^unbiased, with 63 bits after the point.
+    let mut unbiased = exp.saturating_sub(zeros as i32).saturating_add(63);
+    let shift = 64 - T::PRECISION + T::MIN_EXP.saturating_sub(unbiased).max(0) as u32;
+    let (mut kept, rest, half) = match shift {
+        0..=63 => (
+            mantissa >> shift,
+            mantissa & ((1 << shift) - 1),
+            1 << (shift - 1),
+        ),
+        64 => (0, mantissa, 1 << 63),
+        _ => (0, 0, 1),
+    };
+    if rest > half || (rest == half && (sticky || kept & 1 == 1)) {
+        kept += 1;
+        if kept == 1 << T::PRECISION {
+            kept >>= 1;
+            unbiased += 1;
+        }
+    }
+    if unbiased < T::MIN_EXP {
+        // Subnormal, or rounded up to the smallest normal number, which has
+        // the same bits.
+        return T::from_bits64(kept);
+    }
+    if unbiased > T::MAX_EXP {
+        return T::from_bits64((T::MAX_EXP as u64 * 2 + 1) << (T::PRECISION - 1));
+    }
+    let biased = (unbiased + T::MAX_EXP) as u64;
+    T::from_bits64(biased << (T::PRECISION - 1) | (kept & ((1 << (T::PRECISION - 1)) - 1)))
+}
+
+/// Walks over a `Source` a character at a time, noting where it looked past
+/// the end if it ever did.
+struct Scan<'a, S: ?Sized> {
+    src: &'a S,
+    at: usize,
+    hit_end: Option<usize>,
+}
+
+impl<S: Source + ?Sized> Scan<'_, S> {
+    fn peek(&mut self) -> Option<char> {
+        let c = self.src.char_at(self.at).map(|(c, _)| c);
+        if c.is_none() {
+            self.hit_end = Some(self.at);
+        }
+        c
+    }
+
+    fn eat(&mut self, pred: impl Fn(char) -> bool) -> Option<char> {
+        let c = self.peek().filter(|&c| pred(c))?;
+        self.at += self.src.char_at(self.at).unwrap().1;
+        Some(c)
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.at;
+        self.at = self.src.digits_end(start);
+        self.peek();
+        self.at - start
+    }
+
+    /// An exponent's sign and decimal digits, saturating far beyond any
+    /// float's range.
+    fn exponent(&mut self) -> Option<i32> {
+        let negative = self.eat(|c| c == '+' || c == '-') == Some('-');
+        let mut exp: i32 = 0;
+        let mut any = false;
+        while let Some(c) = self.eat(|c| c.is_ascii_digit()) {
+            exp = (exp * 10 + c.to_digit(10).unwrap() as i32).min(1 << 20);
+            any = true;
+        }
+        any.then_some(if negative { -exp } else { exp })
+    }
+
+    fn hex_float<T: Float>(&mut self) -> Option<T> {
+        self.eat(|c| c == '0')?;
+        self.eat(|c| c == 'x' || c == 'X')?;
+        let (mut mantissa, mut exp, mut sticky) = (0_u64, 0_i32, false);
+        let (mut any, mut point) = (false, false);
+        loop {
+            if let Some(c) = self.eat(|c| c.is_ascii_hexdigit()) {
+                let digit = c.to_digit(16).unwrap() as u64;
+                if mantissa >> 60 == 0 {
+                    mantissa = mantissa << 4 | digit;
+                    exp = exp.saturating_sub(if point { 4 } else { 0 });
+                } else {
+                    sticky |= digit != 0;
+                    exp = exp.saturating_add(if point { 0 } else { 4 });
+                }
+                any = true;
+            } else if !point && self.eat(|c| c == '.').is_some() {
+                point = true;
+            } else {
+                break;
+            }
+        }
+        self.eat(|c| c == 'p' || c == 'P').filter(|_| any)?;
+        let exp = exp.saturating_add(self.exponent()?);
+        Some(from_binary(mantissa, exp, sticky))
+    }
+
+    fn special<T: Float>(&mut self) -> Option<T> {
+        let start = self.at;
+        for word in ["infinity", "inf", "nan"] {
+            self.at = start;
+            if word
+                .chars()
+                .all(|w| self.eat(|c| c.eq_ignore_ascii_case(&w)).is_some())
+            {
+                return word.parse().ok();
+            }
+        }
+        self.at = start;
+        None
+    }
+
+    /// Skips a decimal number, returning whether there was one.
+    fn decimal(&mut self) -> bool {
+        let mut digits = self.digits();
+        if self.eat(|c| c == '.').is_some() {
+            digits += self.digits();
+        }
+        if digits == 0 {
+            return false;
+        }
+        let mantissa_end = self.at;
+        if self.eat(|c| c == 'e' || c == 'E').is_none() || self.exponent().is_none() {
+            self.at = mantissa_end;
+        }
+        true
+    }
+}
+
+/// The syntax of a floating-point number. By default it's the decimal
+/// syntax `-12.5e-3`, `.5` and `1.` included; the builder methods enable
+/// more. Both libraries' float parsers scan with it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FloatFormat {
+    special: bool,
+    plus: bool,
+    hex: bool,
+}
+
+impl FloatFormat {
+    pub fn new() -> Self {
+        FloatFormat::default()
+    }
+
+    /// Also accepts `inf`, `infinity` and `nan`, in any case.
+    pub fn special(mut self) -> Self {
+        self.special = true;
+        self
+    }
+
+    /// Also accepts a leading `+`.
+    pub fn plus(mut self) -> Self {
+        self.plus = true;
+        self
+    }
+
+    /// Also accepts hexadecimal floats such as `0x1.8p3`, where the binary
+    /// exponent is required.
+    pub fn hex(mut self) -> Self {
+        self.hex = true;
+        self
+    }
+
+    /// Scans a number at `start` and converts it with correct rounding,
+    /// returning it and the offset it ends at. `partial` says more text may
+    /// follow the end of `src`.
+    pub fn scan<T: Float, S: Source + ?Sized>(
+        &self,
+        src: &S,
+        start: usize,
+        partial: bool,
+    ) -> Result<(T, usize), ScanError> {
+        let mut scan = Scan {
+            src,
+            at: start,
+            hit_end: None,
+        };
+        let negative = match scan.eat(|c| c == '-' || (self.plus && c == '+')) {
+            Some(sign) => sign == '-',
+            None => false,
+        };
+        let unsigned = scan.at;
+        let sign = |v: T| if negative { -v } else { v };
+        let value = if let Some(v) = self.hex.then(|| scan.hex_float()).flatten() {
+            Some(sign(v))
+        } else if let Some(v) = self.special.then(|| scan.special()).flatten() {
+            Some(sign(v))
+        } else {
+            scan.at = unsigned;
+            scan.decimal()
+                .then(|| src.parse_text(start, scan.at))
+                .flatten()
+        };
+        if let Some(end) = scan.hit_end.filter(|_| partial) {
+            return Err(ScanError::Incomplete(end));
+        }
+        match value {
+            Some(v) => Ok((v, scan.at)),
+            None => Err(ScanError::Expected(unsigned, "number")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_integer() {
+        let decimal = IntegerFormat::new(10).signed();
+        assert_eq!(Ok((-12_i8, 3)), decimal.scan("-12x", 0, false));
+        assert_eq!(Ok((7_u8, 4)), decimal.scan("ab+7", 2, false));
+        let overflow = decimal.scan::<i8, _>("-129", 0, false);
+        assert_eq!(Err(ScanError::Expected(0, "integer in i8 range")), overflow);
+        let bits = IntegerFormat::new(2).underscores();
+        assert_eq!(Ok((0b101_u8, 4)), bits.scan("10_1_", 0, false));
+        assert_eq!(
+            Err(ScanError::Incomplete(5)),
+            bits.scan::<u8, _>("10_1_", 0, true)
+        );
+        let err = IntegerFormat::new(16).scan::<u8, _>("_1", 0, false);
+        assert_eq!(Err(ScanError::Expected(0, "hex digit")), err);
+    }
+
+    #[test]
+    fn test_scan_float() {
+        let format = FloatFormat::new();
+        assert_eq!(Ok((-2.5e-3_f64, 7)), format.scan("-2.5e-3,", 0, false));
+        assert_eq!(Ok((0.5_f32, 3)), format.scan("x.5", 1, false));
+        let err = format.scan::<f64, _>("-.e1", 0, false);
+        assert_eq!(Err(ScanError::Expected(1, "number")), err);
+        let err = format.scan::<f64, _>("1.5e", 0, true);
+        assert_eq!(Err(ScanError::Incomplete(4)), err);
+        let hex = FloatFormat::new().hex().plus();
+        assert_eq!(Ok((12.0_f64, 8)), hex.scan("+0x1.8p3", 0, false));
+        assert_eq!(Ok((f64::from_bits(1), 9)), hex.scan("0x1p-1074", 0, false));
+    }
+
+    #[test]
+    fn test_parse_text() {
+        /// A source that isn't a `str`, to exercise the default methods.
+        struct Spread<'a>(&'a [char]);
+
+        impl Source for Spread<'_> {
+            fn char_at(&self, at: usize) -> Option<(char, usize)> {
+                self.0.get(at).map(|&c| (c, 1))
+            }
+        }
+
+        let chars: Vec<char> = "12.25]".chars().collect();
+        let (v, end) = FloatFormat::new()
+            .scan::<f64, _>(&Spread(&chars), 0, false)
+            .unwrap();
+        assert_eq!((12.25, 5), (v, end));
+    }
+}
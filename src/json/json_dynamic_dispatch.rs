@@ -2,59 +2,78 @@ use crate::json::Json;
 use dynamicparse::combinator::*;
 use dynamicparse::helper::*;
 use dynamicparse::Parser;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::rc::Rc;
 
-fn lazy<'a, T: 'a>(f: impl Fn() -> Parser<'a, T> + 'a) -> Parser<'a, T> {
-    Rc::new(move |s| f()(s))
-}
-
-pub fn parse(s: &str) -> Option<Json> {
-    element()(s).map(|(json, _)| json)
+/// Parses a complete JSON document, rendering any failure as a diagnostic
+/// that points into `s`.
+pub fn parse(s: &str) -> Result<Json, String> {
+    dynamicparse::parse(&and(element(), end()), s)
+        .map(|((json, _), _)| json)
+        .map_err(|e| e.render(s))
 }
 
 fn element<'a>() -> Parser<'a, Json> {
-    lazy(|| between(value(), ws(), ws()))
+    recursive(|element| between(value(element), ws(), ws()))
 }
 
-pub fn value<'a>() -> Parser<'a, Json> {
-    or(
-        or(or(or(or(null(), boolean()), number()), string()), array()),
-        object(),
+pub fn value<'a>(element: Parser<'a, Json>) -> Parser<'a, Json> {
+    label(
+        or(
+            or(
+                or(or(or(null(), boolean()), number()), string()),
+                array(element.clone()),
+            ),
+            object(element),
+        ),
+        "value",
     )
 }
 
-pub fn object<'a>() -> Parser<'a, Json> {
-    let pair = map(
-        and(
-            and(and(and(ws(), string()), ws()), character(':')),
-            element(),
+pub fn object<'a>(element: Parser<'a, Json>) -> Parser<'a, Json> {
+    let pair = label(
+        map(
+            and(and(and(string(), ws()), character(':')), element),
+            |(((key, _), _), value)| match key {
+                Json::String(key) => (key, value),
+                _ => panic!("we shouldn't be here."),
+            },
         ),
-        |((((_, key), _), _), value)| match key {
-            Json::String(key) => (key, value),
-            _ => panic!("we shouldn't be here."),
-        },
+        "object member",
     );
-    let members = sep_by(pair, character(','));
-    let empty_obj = map(between(ws(), character('{'), character('}')), |_| {
+    // Both alternatives below start by skipping whitespace after the '{'.
+    let leading_ws = memo(ws());
+    let members = sep_by(
+        cut(map(and(leading_ws.clone(), pair), |(_, pair)| pair)),
+        character(','),
+    );
+    let empty_obj = map(and(leading_ws, character('}')), |_| {
         HashMap::<String, Json>::new()
     });
-    let non_empty_obj = map(between(members, character('{'), character('}')), |x| {
+    let non_empty_obj = map(and(members, character('}')), |(x, _)| {
         x.into_iter().collect::<HashMap<String, Json>>()
     });
-    map(or(empty_obj, non_empty_obj), |v| Json::Object(v))
+    let obj = map(
+        and(character('{'), cut(or(empty_obj, non_empty_obj))),
+        |(_, obj)| Json::Object(obj),
+    );
+    context(obj, "object")
 }
 
-pub fn array<'a>() -> Parser<'a, Json> {
-    let empty_arr = map(between(ws(), character('['), character(']')), |_| {
-        Vec::<Json>::new()
-    });
-    let non_empty_arr = between(
-        sep_by(element(), character(',')),
-        character('['),
-        character(']'),
+pub fn array<'a>(element: Parser<'a, Json>) -> Parser<'a, Json> {
+    let empty_arr = map(and(ws(), character(']')), |_| Vec::<Json>::new());
+    let non_empty_arr = map(
+        and(
+            sep_by(cut(label(element, "value")), character(',')),
+            character(']'),
+        ),
+        |(vs, _)| vs,
     );
-    map(or(empty_arr, non_empty_arr), |vs| Json::Array(vs))
+    let arr = map(
+        and(character('['), cut(or(empty_arr, non_empty_arr))),
+        |(_, vs)| Json::Array(vs),
+    );
+    context(arr, "array")
 }
 
 pub fn null<'a>() -> Parser<'a, Json> {
@@ -67,26 +86,37 @@ pub fn boolean<'a>() -> Parser<'a, Json> {
     })
 }
 
-pub fn string<'a>() -> Parser<'a, Json> {
-    let hex = or(
-        or(digit(), map(character_range('a'..='f'), |c| c as u8)),
-        map(character_range('A'..='F'), |c| c as u8),
+/// A `\u` escape, after its backslash. Characters beyond the Basic
+/// Multilingual Plane are written as a surrogate pair of escapes; half a pair
+/// on its own is an error.
+fn unicode_escape<'a>() -> Parser<'a, char> {
+    let hex = map(
+        or(
+            or(character_range('0'..='9'), character_range('a'..='f')),
+            character_range('A'..='F'),
+        ),
+        |c| c.to_digit(16).unwrap(),
     );
-
-    let unicode = map(
+    let unit = map(
         and(
             and(
                 and(and(character('u'), hex.clone()), hex.clone()),
                 hex.clone(),
             ),
-            hex.clone(),
+            hex,
         ),
-        |((((_, a), b), c), d)| {
-            let unicode = format!("{:X}{:X}{:X}{:X}", a, b, c, d);
-            let unicode = u32::from_str_radix(&unicode, 16).unwrap();
-            char::from_u32(unicode).unwrap()
-        },
+        |((((_, a), b), c), d)| a << 12 | b << 8 | c << 4 | d,
     );
+    let high = filter(unit.clone(), |u| (0xd800..0xdc00).contains(u));
+    let low = filter(unit.clone(), |u| (0xdc00..0xe000).contains(u));
+    let pair = map(and(high, and(character('\\'), low)), |(high, (_, low))| {
+        0x10000 + ((high - 0xd800) << 10 | (low - 0xdc00))
+    });
+    let single = filter(unit, |u| !(0xd800..0xe000).contains(u));
+    map(or(pair, single), |u| char::from_u32(u).unwrap())
+}
+
+pub fn string<'a>() -> Parser<'a, Json> {
     let escape = or(
         or(
             or(
@@ -104,14 +134,11 @@ pub fn string<'a>() -> Parser<'a, Json> {
             ),
             character('t'),
         ),
-        unicode,
+        unicode_escape(),
     );
 
-    let json_valid_chars = except(
-        character_range('\u{0020}'..='\u{10FFFF}'),
-        or(character('"'), character('\\')),
-    );
-    let json_valid_escape = map(and(character('\\'), escape), |(_, b)| match b {
+    let json_valid_char = |c: &char| *c >= '\u{0020}' && *c != '"' && *c != '\\';
+    let json_valid_escape = map(and(character('\\'), cut(escape)), |(_, b)| match b {
         'b' => '\u{0008}',
         'f' => '\u{000C}',
         'n' => '\n',
@@ -119,50 +146,97 @@ pub fn string<'a>() -> Parser<'a, Json> {
         't' => '\t',
         _ => b,
     });
-    let json_character = or(json_valid_chars, json_valid_escape);
-    let characters = many(json_character);
+    // Plain runs are sliced out of the input rather than collected a char at
+    // a time.
+    let plain = map(take_while1(json_valid_char), Cow::Borrowed);
+    let escaped = map(json_valid_escape, |c| Cow::Owned(c.to_string()));
+    let characters = many(or(plain, escaped));
 
-    map(
-        and(and(character('"'), characters), character('"')),
-        |((_, vs), _)| Json::String(String::from_iter(vs)),
+    context(
+        map(
+            and(character('"'), cut(and(characters, character('"')))),
+            |(_, (pieces, _))| Json::String(pieces.concat()),
+        ),
+        "string",
     )
 }
 
 pub fn number<'a>() -> Parser<'a, Json> {
-    let digits = map(many1(digit()), |ds| {
-        ds.iter().fold(0_i32, |acc, &digit| acc * 10 + digit as i32)
-    });
+    let digits = skip(many1(skip(digit())));
     let sign = or(character('+'), character('-'));
-    let integer = or(
-        digits.clone(),
-        map(and(character('-'), digits.clone()), |(_, number)| -number),
-    );
-    let fraction = map(and(skip(character('.')), digits.clone()), |(_, num)| num);
-    let exponent = and(
-        skip(or(character('E'), character('e'))),
-        map(and(sign, digits.clone()), |(s, n)| match s {
-            '-' => -n,
-            _ => n,
-        }),
-    );
-    let p = map(
-        and(and(integer, optional(fraction)), optional(exponent)),
-        |((n, f), e)| match (n, f, e) {
-            (n, Some(f), Some((_, e))) => format!("{}.{}E{}", n, f, e)
-                .parse::<f64>()
-                .expect("couldn't parse number"),
-            (n, Some(f), _) => format!("{}.{}", n, f)
-                .parse::<f64>()
-                .expect("couldn't parse number"),
-            (n, _, _) => f64::from(n),
-        },
-    );
-    map(p, |v| Json::Number(v))
+    let integer = or(digits.clone(), skip(and(character('-'), digits.clone())));
+    let fraction = and(character('.'), digits.clone());
+    let exponent = and(and(or(character('E'), character('e')), sign), digits);
+    let p = recognize(and(and(integer, optional(fraction)), optional(exponent)));
+    map(p, |n| {
+        Json::Number(n.parse::<f64>().expect("couldn't parse number"))
+    })
 }
 
 fn ws<'a>() -> Parser<'a, ()> {
-    skip(many(or(
-        or(or(character(' '), character('\n')), character('\r')),
-        character('\t'),
-    )))
+    skip(take_while(|c| matches!(c, ' ' | '\n' | '\r' | '\t')))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::json_dynamic_dispatch::*;
+    use dynamicparse::{Expected, Status};
+
+    #[test]
+    fn test_parse_labels() {
+        let err = dynamicparse::parse(&element(), "[{\"a\": \"b\\q\"}]").unwrap_err();
+        assert_eq!(&["array", "object", "string"], err.context());
+        let err = dynamicparse::parse(&object(element()), "{ 1 }").unwrap_err();
+        assert_eq!(
+            &[Expected::Char('}'), Expected::Label("object member")],
+            err.expected()
+        );
+    }
+
+    #[test]
+    fn test_parse_cut() {
+        let err = dynamicparse::parse(&object(element()), "{\"a\": 1,}").unwrap_err();
+        assert!(err.is_committed());
+        assert_eq!(8, err.offset());
+        assert_eq!(&[Expected::Label("object member")], err.expected());
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let status = dynamicparse::parse_partial(&element(), "[1, tr").unwrap();
+        assert_eq!(Status::Incomplete { needed: 2 }, status);
+        let status = dynamicparse::parse_partial(&element(), "[1, true],").unwrap();
+        let json = Json::Array(vec![Json::Number(1_f64), Json::Bool(true)]);
+        assert_eq!(Status::Done(json, ","), status);
+        assert!(dynamicparse::parse_partial(&element(), "[1, x").is_err());
+    }
+
+    #[test]
+    fn test_parse_unicode_escapes() {
+        let string = |s| dynamicparse::parse(&string(), s).map(|(v, _)| v);
+        assert_eq!(
+            Ok(Json::String("éÉ".to_owned())),
+            string("\"\\u00e9\\u00C9\"")
+        );
+        let emoji = string("\"\\ud83d\\ude00!\"");
+        assert_eq!(Ok(Json::String("\u{1f600}!".to_owned())), emoji);
+        assert!(string("\"\\ud83d\"").is_err());
+        assert!(string("\"\\ud83d\\u0041\"").is_err());
+        assert!(string("\"\\ude00\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_comma() {
+        let err = parse("{\"a\": 1 \"b\": 2}").unwrap_err();
+        assert!(err.starts_with("error: expected ',' or '}' after object member, found '\"'\n"));
+        let expected = concat!(
+            "error: expected ',' or ']' after value, found '2'\n",
+            " --> 1:4\n",
+            "  |\n",
+            "1 | [1 2]\n",
+            "  |    ^\n",
+            "  = note: in array\n",
+        );
+        assert_eq!(Err(expected.to_owned()), parse("[1 2]"));
+    }
 }
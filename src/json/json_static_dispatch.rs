@@ -1,82 +1,211 @@
-use crate::json::Json;
+use crate::json::{Json, JsonRef};
+use staticparse::async_reader::{AsyncRead, AsyncReader};
 use staticparse::helper::*;
-use staticparse::Parser;
+use staticparse::reader::Reader;
+use staticparse::{ParseResult, Parser, State};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::io::Read;
 
-pub fn parse(s: &str) -> Option<Json> {
-    element().parse(s).map(|(json, _)| json)
+/// Parses a complete JSON document, rendering any failure as a diagnostic
+/// that points into `s`.
+pub fn parse(s: &str) -> Result<Json, String> {
+    parse_borrowed(s).map(|json| json.to_owned())
 }
 
-#[derive(Clone)]
-pub struct ElementParser;
-impl Parser<Json> for ElementParser {
-    fn parse<'a>(&self, s: &'a str) -> Option<(Json, &'a str)> {
-        ws().and_then(value())
-            .and_then(ws())
-            .map(|((_, v), _)| v)
-            .parse(s)
-    }
+/// Like `parse`, but strings without escapes are left borrowed from `s`
+/// rather than copied.
+pub fn parse_borrowed(s: &str) -> Result<JsonRef<'_>, String> {
+    element()
+        .and_then(end())
+        .parse(s)
+        .map(|((json, _), _)| json)
+        .map_err(|e| e.render(s))
+}
+
+/// Parses JSON documents straight from `reader`, one after another, without
+/// reading the source into a `String` first. A single document works as
+/// well as a newline-delimited log; only the documents being parsed are
+/// buffered, so the source can be far bigger than memory.
+pub fn read_values<R: Read>(
+    reader: &mut Reader<R>,
+) -> impl Iterator<Item = Result<Json, String>> + '_ {
+    reader.records(documents).flat_map(|batch| {
+        let (values, error) = match batch {
+            Ok(values) => (values, None),
+            Err(e) => (Vec::new(), Some(Err(e.to_string()))),
+        };
+        values.into_iter().map(Ok).chain(error)
+    })
 }
-pub fn element() -> ElementParser {
-    ElementParser
+
+/// The async counterpart of `read_values`: hands each JSON document in
+/// `reader` to `each` as soon as its last byte has arrived, stopping at the
+/// first one that fails.
+pub async fn read_values_async<R: AsyncRead + Unpin>(
+    reader: &mut AsyncReader<R>,
+    mut each: impl FnMut(Json),
+) -> Result<(), String> {
+    let mut batches = reader.records(documents);
+    while let Some(batch) = batches.next().await {
+        batch
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .for_each(&mut each);
+    }
+    Ok(())
 }
 
-#[derive(Clone)]
-pub struct ValueParser;
-impl Parser<Json> for ValueParser {
-    fn parse<'a>(&self, s: &'a str) -> Option<(Json, &'a str)> {
-        token("null")
-            .map(|_| Json::Null)
-            .or_else(boolean().map(Json::Bool))
-            .or_else(number().map(Json::Number))
-            .or_else(string().map(Json::String))
-            .or_else(array().map(Json::Array))
-            .or_else(object().map(Json::Object))
-            .parse(s)
+/// Every complete document at the start of `s`, all parsed with one copy of
+/// the grammar. The grammar borrows the buffer it reads from, so it can't
+/// outlive a single run of the reader; this makes each run count. A document
+/// that fails or runs off the end of the buffer ends the batch, unless it's
+/// the first, and is parsed again by the next run.
+fn documents<'s, 'a>(mut s: State<'s, &'a str>) -> ParseResult<'s, &'a str, Vec<Json>> {
+    let document = document();
+    let mut values = Vec::new();
+    loop {
+        match document.parse_at(s.clone()) {
+            Ok((Some(json), r)) => {
+                values.push(json);
+                s = r;
+            }
+            Ok((None, r)) => return Ok((values, r)),
+            Err(_) if !values.is_empty() => return Ok((values, s)),
+            Err(e) => return Err(e),
+        }
     }
 }
-pub fn value() -> ValueParser {
-    ValueParser
+
+/// Skips whitespace up to the next document, if there is one. Whitespace
+/// after a document is left alone so it can be handed out without waiting
+/// for whatever comes next.
+fn document<'a>() -> impl Parser<&'a str, Option<Json>> {
+    ws().and_then(
+        value(element())
+            .map(|json| Some(json.to_owned()))
+            .or_else(end().map(|_| None)),
+    )
+    .map(|(_, json)| json)
+}
+
+/// Parses a JSON document, skipping over malformed array elements and object
+/// members so that every mistake in `s` gets reported, not just the first.
+pub fn parse_recovery(s: &str) -> (Option<Json>, Vec<String>) {
+    let (json, errors) = element()
+        .and_then(end())
+        .map(|(json, _)| json.to_owned())
+        .parse_recovery(s);
+    (json, errors.iter().map(|e| e.render(s)).collect())
 }
 
-pub fn object() -> impl Parser<HashMap<String, Json>> + Clone {
-    let pair = ws()
-        .and_then(string())
+pub fn element<'a>() -> impl Parser<&'a str, JsonRef<'a>> + Clone {
+    recursive(|element| {
+        ws().and_then(value(element))
+            .and_then(ws())
+            .map(|((_, v), _)| v)
+    })
+}
+
+pub fn value<'a>(
+    element: impl Parser<&'a str, JsonRef<'a>> + Clone,
+) -> impl Parser<&'a str, JsonRef<'a>> + Clone {
+    token("null")
+        .map(|_| JsonRef::Null)
+        .or_else(boolean().map(JsonRef::Bool))
+        .or_else(number().map(JsonRef::Number))
+        .or_else(string().map(JsonRef::String))
+        .or_else(array(element.clone()).map(JsonRef::Array))
+        .or_else(object(element).map(JsonRef::Object))
+        .label("value")
+}
+
+pub fn object<'a>(
+    element: impl Parser<&'a str, JsonRef<'a>> + Clone,
+) -> impl Parser<&'a str, HashMap<Cow<'a, str>, JsonRef<'a>>> + Clone {
+    let pair = string()
         .and_then(ws())
         .and_then(character(':'))
-        .and_then(element())
-        .map(|((((_, key), _), _), value)| (key, value));
-    let members = pair.sep_by(character(','));
-    let empty_obj = ws()
-        .between(character('{'), character('}'))
-        .map(|_| HashMap::<String, Json>::new());
+        .and_then(element)
+        .map(|(((key, _), _), value)| (key, value))
+        .label("object member");
+    // Both alternatives below start by skipping whitespace after the '{'.
+    let leading_ws = ws().memo();
+    let members = leading_ws
+        .clone()
+        .and_then(pair)
+        .map(|(_, pair)| Some(pair))
+        .recover_with(skip_balanced("[]{}", ",}").map(|_| None))
+        .cut()
+        .sep_by(character(','));
+    let empty_obj = leading_ws.and_then(character('}')).map(|_| HashMap::new());
     let non_empty_obj = members
-        .between(character('{'), character('}'))
-        .map(|x| x.into_iter().collect());
-    empty_obj.or_else(non_empty_obj)
+        .and_then(closing('}'))
+        .map(|(x, _)| x.into_iter().flatten().collect());
+    character('{')
+        .and_then(empty_obj.or_else(non_empty_obj).cut())
+        .map(|(_, obj)| obj)
+        .context("object")
 }
 
-pub fn array() -> impl Parser<Vec<Json>> + Clone {
-    let empty_arr = ws()
-        .between(character('['), character(']'))
-        .map(|_| Vec::<Json>::new());
-    let non_empty_arr = element()
+pub fn array<'a>(
+    element: impl Parser<&'a str, JsonRef<'a>> + Clone,
+) -> impl Parser<&'a str, Vec<JsonRef<'a>>> + Clone {
+    let empty_arr = ws().and_then(character(']')).map(|_| Vec::new());
+    let non_empty_arr = element
+        .label("value")
+        .recover_with(skip_balanced("[]{}", ",]").map(|_| JsonRef::Null))
+        .cut()
         .sep_by(character(','))
-        .between(character('['), character(']'));
-    empty_arr.or_else(non_empty_arr)
+        .and_then(closing(']'))
+        .map(|(vs, _)| vs);
+    character('[')
+        .and_then(empty_arr.or_else(non_empty_arr).cut())
+        .map(|(_, vs)| vs)
+        .context("array")
 }
 
-pub fn boolean() -> impl Parser<bool> + Clone {
+/// The `close` bracket of a non-empty container. When it's missing, recovery
+/// drops the rest of the container, nested ones included.
+fn closing<'a>(close: char) -> impl Parser<&'a str, char> + Clone {
+    let stop = close.to_string();
+    character(close).recover_with(
+        skip_balanced("[]{}", &stop)
+            .and_then(character(close))
+            .map(|(_, c)| c),
+    )
+}
+
+pub fn boolean<'a>() -> impl Parser<&'a str, bool> + Clone {
     token("true")
         .or_else(token("false"))
         .map(|parsed| parsed.parse::<bool>().expect("couldn't parse bool"))
 }
 
-pub fn string() -> impl Parser<String> + Clone {
-    let hex = digit()
-        .or_else(character_range('a'..='f').map(|c| c as u8))
-        .or_else(character_range('A'..='F').map(|c| c as u8));
+/// A `\u` escape, after its backslash. Characters beyond the Basic
+/// Multilingual Plane are written as a surrogate pair of escapes; half a pair
+/// on its own is an error.
+fn unicode_escape<'a>() -> impl Parser<&'a str, char> + Clone {
+    let hex = character_range('0'..='9')
+        .or_else(character_range('a'..='f'))
+        .or_else(character_range('A'..='F'))
+        .map(|c| c.to_digit(16).unwrap());
+    let unit = character('u')
+        .and_then(hex.clone())
+        .and_then(hex.clone())
+        .and_then(hex.clone())
+        .and_then(hex)
+        .map(|((((_, a), b), c), d)| a << 12 | b << 8 | c << 4 | d);
+    let high = unit.clone().filter(|u| (0xd800..0xdc00).contains(u));
+    let low = unit.clone().filter(|u| (0xdc00..0xe000).contains(u));
+    let pair = high
+        .and_then(character('\\').and_then(low))
+        .map(|(high, (_, low))| 0x10000 + ((high - 0xd800) << 10 | (low - 0xdc00)));
+    let single = unit.filter(|u| !(0xd800..0xe000).contains(u));
+    pair.or_else(single).map(|u| char::from_u32(u).unwrap())
+}
 
+pub fn string<'a>() -> impl Parser<&'a str, Cow<'a, str>> + Clone {
     let escape = character('"')
         .or_else(character('\\'))
         .or_else(character('/'))
@@ -85,91 +214,68 @@ pub fn string() -> impl Parser<String> + Clone {
         .or_else(character('n'))
         .or_else(character('r'))
         .or_else(character('t'))
-        .or_else(
-            character('u')
-                .and_then(hex.clone())
-                .and_then(hex.clone())
-                .and_then(hex.clone())
-                .and_then(hex.clone())
-                .map(|((((_, a), b), c), d)| {
-                    let unicode = format!("{:X}{:X}{:X}{:X}", a, b, c, d);
-                    let unicode = u32::from_str_radix(&unicode, 16).unwrap();
-                    char::from_u32(unicode).unwrap()
-                }),
-        );
+        .or_else(unicode_escape());
 
-    let json_valid_chars =
-        character_range('\u{0020}'..='\u{10FFFF}').except(character('"').or_else(character('\\')));
+    let json_valid_char = |c: &char| *c >= '\u{0020}' && *c != '"' && *c != '\\';
 
-    let json_valid_escape = character('\\').and_then(escape).map(|(_, b)| match b {
-        'b' => '\u{0008}',
-        'f' => '\u{000C}',
-        'n' => '\n',
-        'r' => '\r',
-        't' => '\t',
-        _ => b,
-    });
+    let json_valid_escape = character('\\')
+        .and_then(escape.cut())
+        .map(|(_, b)| match b {
+            'b' => '\u{0008}',
+            'f' => '\u{000C}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            _ => b,
+        });
 
-    let json_character = json_valid_chars.or_else(json_valid_escape);
+    // Runs of plain characters are borrowed straight from the input; only
+    // escapes need a buffer of their own.
+    let plain = take_while1(json_valid_char).map(Cow::Borrowed);
+    let escaped = json_valid_escape.map(|c| Cow::Owned(c.to_string()));
 
-    let characters = json_character.many();
+    let characters = plain.or_else(escaped).many();
     character('"')
-        .and_then(characters)
-        .and_then(character('"'))
-        .map(|((_, vs), _)| String::from_iter(vs))
+        .and_then(characters.and_then(character('"')).cut())
+        .map(|(_, (mut pieces, _))| match pieces.len() {
+            0 | 1 => pieces.pop().unwrap_or_default(),
+            _ => Cow::Owned(pieces.concat()),
+        })
+        .context("string")
 }
 
-pub fn number() -> impl Parser<f64> + Clone {
-    let digits = digit()
-        .many1()
-        .map(|ds| ds.iter().fold(0_i32, |acc, &digit| acc * 10 + digit as i32));
+pub fn number<'a>() -> impl Parser<&'a str, f64> + Clone {
+    let digits = digit().skip().many1().skip();
 
     let sign = character('+').or_else(character('-'));
-    let integer = digits.clone().or_else(
-        character('-')
-            .and_then(digits.clone())
-            .map(|(_, number)| -number),
-    );
-    let fraction = character('.')
-        .skip()
-        .and_then(digits.clone())
-        .map(|(_, num)| num);
-    let exponent =
-        character('E')
-            .or_else(character('e'))
-            .skip()
-            .and_then(sign.and_then(digits.clone()).map(|(s, n)| match s {
-                '-' => -n,
-                _ => n,
-            }));
+    let integer = digits
+        .clone()
+        .or_else(character('-').and_then(digits.clone()).skip());
+    let fraction = character('.').and_then(digits.clone());
+    let exponent = character('E')
+        .or_else(character('e'))
+        .and_then(sign)
+        .and_then(digits);
 
     integer
         .and_then(fraction.optional())
         .and_then(exponent.optional())
-        .map(|((n, f), e)| match (n, f, e) {
-            (n, Some(f), Some((_, e))) => format!("{}.{}E{}", n, f, e)
-                .parse::<f64>()
-                .expect("couldn't parse number"),
-            (n, Some(f), _) => format!("{}.{}", n, f)
-                .parse::<f64>()
-                .expect("couldn't parse number"),
-            (n, _, _) => f64::from(n),
-        })
+        .recognize()
+        .map(|n: &str| n.parse::<f64>().expect("couldn't parse number"))
 }
 
-fn ws() -> impl Parser<()> + Clone {
-    character(' ')
-        .or_else(character('\n'))
-        .or_else(character('\r'))
-        .or_else(character('\t'))
-        .many()
-        .skip()
+fn ws<'a>() -> impl Parser<&'a str, ()> + Clone {
+    take_while(|c: &char| matches!(c, ' ' | '\n' | '\r' | '\t')).skip()
 }
 
 #[cfg(test)]
 mod tests {
     use crate::json::json_static_dispatch::*;
-    use staticparse::Parser;
+    use staticparse::async_reader::{block_on, Stream, StreamReader};
+    use staticparse::{Expected, Parser, Status};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
 
     #[test]
     fn test_parse_ws() {
@@ -195,41 +301,320 @@ mod tests {
     fn test_parse_string() {
         let s = "\"hello\"1";
         let (v, r) = string().parse(s).unwrap();
-        assert_eq!("hello", v.as_str());
+        assert!(matches!(v, Cow::Borrowed("hello")));
         assert_eq!("1", r);
+        let (v, _) = string().parse("\"a\\nb\"").unwrap();
+        assert!(matches!(v, Cow::Owned(v) if v == "a\nb"));
+    }
+
+    #[test]
+    fn test_parse_unicode_escapes() {
+        let (v, _) = string().parse("\"\\u00e9\\u00C9\"").unwrap();
+        assert_eq!("éÉ", v);
+        let (v, _) = string().parse("\"\\ud83d\\ude00!\"").unwrap();
+        assert_eq!("\u{1f600}!", v);
+        assert!(string().parse("\"\\ud83d\"").is_err());
+        assert!(string().parse("\"\\ud83d\\u0041\"").is_err());
+        assert!(string().parse("\"\\ude00\"").is_err());
     }
 
     #[test]
     fn test_parse_bool() {
         let s = "true";
         let (v, _) = boolean().parse(s).unwrap();
-        assert_eq!(true, v);
+        assert!(v);
         let s = "false";
         let (v, _) = boolean().parse(s).unwrap();
-        assert_eq!(false, v);
+        assert!(!v);
     }
 
     #[test]
     fn test_parse_array() {
         let s = "[1, [\"a\", false], null]";
-        let (vs, _) = array().parse(s).unwrap();
-        assert_eq!(Json::Number(1_f64), vs[0]);
+        let (vs, _) = array(element()).parse(s).unwrap();
+        assert_eq!(JsonRef::Number(1_f64), vs[0]);
         assert_eq!(
-            Json::Array(vec![Json::String("a".to_string()), Json::Bool(false)]),
+            JsonRef::Array(vec![JsonRef::String("a".into()), JsonRef::Bool(false)]),
             vs[1]
         );
-        assert_eq!(Json::Null, vs[2]);
+        assert_eq!(JsonRef::Null, vs[2]);
     }
 
     #[test]
     fn test_parse_object() {
         let s = "{\"a\": true}";
-        let (obj, _) = object().parse(s).unwrap();
+        let (obj, _) = object(element()).parse(s).unwrap();
         let expected = {
             let mut m = HashMap::new();
-            m.insert("a".to_string(), Json::Bool(true));
+            m.insert("a".into(), JsonRef::Bool(true));
             m
         };
         assert_eq!(expected, obj);
     }
+
+    #[test]
+    fn test_parse_spanned() {
+        let s = "\n  \"hi\"";
+        let ((_, v), _) = ws().and_then(string().spanned()).parse(s).unwrap();
+        assert_eq!("hi", v.value);
+        assert_eq!((3, 7), (v.start, v.end));
+        assert_eq!((2, 3), (v.line, v.column));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = number().parse("x").unwrap_err();
+        assert_eq!(0, err.offset());
+        assert_eq!(
+            &[Expected::Label("digit"), Expected::Char('-')],
+            err.expected()
+        );
+        let err = array(element()).parse("[1, 2 x").unwrap_err();
+        assert_eq!(6, err.offset());
+        assert_eq!(&[Expected::Char(','), Expected::Char(']')], err.expected());
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        let err = element().parse("[{\"a\": \"b\\q\"}]").unwrap_err();
+        assert_eq!(&["array", "object", "string"], err.context());
+        let err = object(element()).parse("{ 1 }").unwrap_err();
+        assert_eq!(
+            &[Expected::Char('}'), Expected::Label("object member")],
+            err.expected()
+        );
+    }
+
+    #[test]
+    fn test_parse_cut() {
+        let err = object(element()).parse("{\"a\": 1,}").unwrap_err();
+        assert!(err.is_committed());
+        assert_eq!(8, err.offset());
+        assert_eq!(&[Expected::Label("object member")], err.expected());
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let status = element().parse_partial("[1, tr").unwrap();
+        assert_eq!(Status::Incomplete { needed: 2 }, status);
+        let status = number().parse_partial("12").unwrap();
+        assert_eq!(Status::Incomplete { needed: 1 }, status);
+        let status = element().parse_partial("[1, true],").unwrap();
+        let json = JsonRef::Array(vec![JsonRef::Number(1_f64), JsonRef::Bool(true)]);
+        assert_eq!(Status::Done(json, ","), status);
+        assert!(element().parse_partial("[1, x").is_err());
+    }
+
+    #[test]
+    fn test_parse_borrowed() {
+        let json = parse_borrowed("{\"a\": [\"b\", \"c\\td\"]}").unwrap();
+        let JsonRef::Object(obj) = &json else {
+            panic!("expected an object, got {:?}", json);
+        };
+        let (key, value) = obj.iter().next().unwrap();
+        assert!(matches!(key, Cow::Borrowed("a")));
+        let JsonRef::Array(vs) = value else {
+            panic!("expected an array, got {:?}", value);
+        };
+        assert!(matches!(vs[0], JsonRef::String(Cow::Borrowed("b"))));
+        assert!(matches!(vs[1], JsonRef::String(Cow::Owned(_))));
+        let owned = HashMap::from([(
+            "a".to_string(),
+            Json::Array(vec![
+                Json::String("b".to_string()),
+                Json::String("c\td".to_string()),
+            ]),
+        )]);
+        assert_eq!(Json::Object(owned), json.to_owned());
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let mut reader = Reader::text("[1, {\"a\": null}]\n".as_bytes());
+        let json = read_values(&mut reader).next().unwrap().unwrap();
+        let obj = HashMap::from([("a".to_string(), Json::Null)]);
+        let expected = Json::Array(vec![Json::Number(1_f64), Json::Object(obj)]);
+        assert_eq!(expected, json);
+        let mut reader = Reader::text("[1, 2 x]".as_bytes());
+        let err = read_values(&mut reader).next().unwrap().unwrap_err();
+        assert_eq!(
+            "expected ',' or ']' after value at offset 6 (in array)",
+            err
+        );
+    }
+
+    #[test]
+    fn test_read_values() {
+        let src = "{\"a\": [1, 2]}\n\"b\"\n  [] 3\n";
+        let mut reader = Reader::text(src.as_bytes());
+        let values: Vec<Json> = read_values(&mut reader).collect::<Result<_, _>>().unwrap();
+        let obj = HashMap::from([(
+            "a".to_string(),
+            Json::Array(vec![Json::Number(1_f64), Json::Number(2_f64)]),
+        )]);
+        let expected = vec![
+            Json::Object(obj),
+            Json::String("b".to_string()),
+            Json::Array(Vec::new()),
+            Json::Number(3_f64),
+        ];
+        assert_eq!(expected, values);
+        let mut reader = Reader::text("1 [2 x".as_bytes());
+        let mut values = read_values(&mut reader);
+        assert_eq!(Some(Ok(Json::Number(1_f64))), values.next());
+        let err = values.next().unwrap().unwrap_err();
+        assert_eq!(
+            "expected ',' or ']' after value at offset 5 (in array)",
+            err
+        );
+        assert_eq!(None, values.next());
+    }
+
+    /// Hands out `src` a few bytes at a time, not ready before each chunk.
+    struct Chunks<'a> {
+        src: &'a [u8],
+        size: usize,
+        ready: bool,
+    }
+
+    impl<'a> Stream for Chunks<'a> {
+        type Item = io::Result<&'a [u8]>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = self.size.min(self.src.len());
+            let (chunk, rest) = self.src.split_at(n);
+            self.src = rest;
+            Poll::Ready((n > 0).then_some(Ok(chunk)))
+        }
+    }
+
+    #[test]
+    fn test_read_values_async() {
+        let src = "{\"wörld\": [1, 2.5e+3, \"\\u0041\"]}\n[true, null] \"x\"\n";
+        for size in 1..5 {
+            let chunks = Chunks {
+                src: src.as_bytes(),
+                size,
+                ready: false,
+            };
+            let mut reader = AsyncReader::text(StreamReader::new(chunks));
+            let mut values = Vec::new();
+            let done = block_on(read_values_async(&mut reader, |json| values.push(json)));
+            assert_eq!(Ok(()), done);
+            let obj = HashMap::from([(
+                "wörld".to_string(),
+                Json::Array(vec![
+                    Json::Number(1_f64),
+                    Json::Number(2500_f64),
+                    Json::String("A".to_string()),
+                ]),
+            )]);
+            let expected = vec![
+                Json::Object(obj),
+                Json::Array(vec![Json::Bool(true), Json::Null]),
+                Json::String("x".to_string()),
+            ];
+            assert_eq!(expected, values);
+        }
+        let chunks = Chunks {
+            src: b"1 [1, 2 x]",
+            size: 3,
+            ready: false,
+        };
+        let mut reader = AsyncReader::text(StreamReader::new(chunks));
+        let mut values = Vec::new();
+        let err = block_on(read_values_async(&mut reader, |json| values.push(json))).unwrap_err();
+        assert_eq!(vec![Json::Number(1_f64)], values);
+        assert_eq!(
+            "expected ',' or ']' after value at offset 8 (in array)",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_large_reader() {
+        let item = "{\"id\": 12345, \"tags\": [\"red\", \"blue\"], \"ok\": true}";
+        let src = format!("[{}]", vec![item; 5000].join(", "));
+        let mut reader = Reader::text(src.as_bytes());
+        let json = read_values(&mut reader).next().unwrap();
+        assert_eq!(parse(&src), json);
+    }
+
+    #[test]
+    fn test_parse_recovery() {
+        let (json, errors) = parse_recovery("{\"a\": [1, tru, 3], 4: 5, \"b\": {}}");
+        let expected = {
+            let mut m = HashMap::new();
+            m.insert(
+                "a".to_string(),
+                Json::Array(vec![Json::Number(1_f64), Json::Null, Json::Number(3_f64)]),
+            );
+            m.insert("b".to_string(), Json::Object(HashMap::new()));
+            Json::Object(m)
+        };
+        assert_eq!(Some(expected), json);
+        assert_eq!(2, errors.len());
+        assert!(errors[0].starts_with("error: expected value, found 't'"));
+        assert!(errors[1].starts_with("error: expected object member, found '4'"));
+    }
+
+    #[test]
+    fn test_parse_recovery_nested() {
+        let (json, errors) = parse_recovery("{\"a\": [1, 2 x], \"b\": tru}");
+        let obj = HashMap::from([(
+            "a".to_string(),
+            Json::Array(vec![Json::Number(1_f64), Json::Number(2_f64)]),
+        )]);
+        assert_eq!(Some(Json::Object(obj)), json);
+        assert_eq!(2, errors.len());
+        assert!(
+            errors[0].starts_with("error: expected ',' or ']' after value, found 'x'\n --> 1:13\n")
+        );
+        assert!(errors[1].starts_with("error: expected value, found 't'\n --> 1:22\n"));
+
+        let (json, errors) = parse_recovery("[[1 2, [3]], {\"a\": [4 5]}, 6]");
+        let expected = Json::Array(vec![
+            Json::Array(vec![Json::Number(1_f64)]),
+            Json::Object(HashMap::from([(
+                "a".to_string(),
+                Json::Array(vec![Json::Number(4_f64)]),
+            )])),
+            Json::Number(6_f64),
+        ]);
+        assert_eq!(Some(expected), json);
+        assert_eq!(2, errors.len());
+        assert!(errors[0].starts_with("error: expected ',' or ']' after value, found '2'"));
+        assert!(errors[1].starts_with("error: expected ',' or ']' after value, found '5'"));
+    }
+
+    #[test]
+    fn test_parse_diagnostic() {
+        let s = "{\n  \"a\": [1, 2 x]\n}";
+        let expected = concat!(
+            "error: expected ',' or ']' after value, found 'x'\n",
+            " --> 2:14\n",
+            "  |\n",
+            "2 |   \"a\": [1, 2 x]\n",
+            "  |              ^\n",
+            "  = note: in object > in array\n",
+        );
+        assert_eq!(Err(expected.to_owned()), parse(s));
+    }
+
+    #[test]
+    fn test_parse_missing_comma() {
+        let err = parse("{\"a\": 1 \"b\": 2}").unwrap_err();
+        assert!(err.starts_with("error: expected ',' or '}' after object member, found '\"'\n"));
+        let err = parse("[1 2]").unwrap_err();
+        assert!(err.starts_with("error: expected ',' or ']' after value, found '2'\n"));
+        let err = element().parse("[true\nnull]").unwrap_err();
+        assert_eq!(6, err.offset());
+        assert_eq!(Some("value"), err.after());
+        assert_eq!(&[Expected::Char(','), Expected::Char(']')], err.expected());
+    }
 }
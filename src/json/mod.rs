@@ -1,6 +1,7 @@
 pub mod json_dynamic_dispatch;
 pub mod json_static_dispatch;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,3 +13,33 @@ pub enum Json {
     Array(Vec<Json>),
     Object(HashMap<String, Json>),
 }
+
+/// A `Json` value whose strings and keys borrow from the source text unless
+/// they had escapes to decode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonRef<'a> {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(Cow<'a, str>),
+    Array(Vec<JsonRef<'a>>),
+    Object(HashMap<Cow<'a, str>, JsonRef<'a>>),
+}
+
+impl JsonRef<'_> {
+    /// Copies out every borrowed string, detaching the value from its source.
+    pub fn to_owned(&self) -> Json {
+        match self {
+            JsonRef::Null => Json::Null,
+            JsonRef::Bool(b) => Json::Bool(*b),
+            JsonRef::Number(n) => Json::Number(*n),
+            JsonRef::String(s) => Json::String(s.to_string()),
+            JsonRef::Array(vs) => Json::Array(vs.iter().map(JsonRef::to_owned).collect()),
+            JsonRef::Object(obj) => Json::Object(
+                obj.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
@@ -1,8 +1,37 @@
 use json::{json_dynamic_dispatch, json_static_dispatch};
+use staticparse::async_reader::{block_on, AsyncReader};
+use staticparse::reader::Reader;
+use std::{env, fs, io, process};
 
 mod json;
 
 fn main() {
+    if let Some(path) = env::args().nth(1) {
+        if path == "-" {
+            let mut reader = Reader::text(io::stdin().lock());
+            for json in json_static_dispatch::read_values(&mut reader) {
+                match json {
+                    Ok(json) => println!("{:?}", json),
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        process::exit(1);
+                    }
+                }
+            }
+            return;
+        }
+        let src = fs::read_to_string(path).expect("couldn't read input file");
+        let (json, diagnostics) = json_static_dispatch::parse_recovery(&src);
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        match json {
+            Some(json) if diagnostics.is_empty() => println!("{:?}", json),
+            _ => process::exit(1),
+        }
+        return;
+    }
+
     let json_str = r#"
     {
       "string": "Hello, World!",
@@ -58,4 +87,12 @@ fn main() {
         json_static_dispatch::parse(json_str),
         json_dynamic_dispatch::parse(json_str)
     );
+    let mut reader = AsyncReader::text(json_str.as_bytes());
+    let mut values = Vec::new();
+    block_on(json_static_dispatch::read_values_async(
+        &mut reader,
+        |json| values.push(json),
+    ))
+    .unwrap();
+    assert_eq!(json_static_dispatch::parse(json_str).ok(), values.pop());
 }
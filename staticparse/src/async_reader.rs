@@ -0,0 +1,350 @@
+use crate::reader::{Buffer, Bytes, Encoding, ReadError, Step, Text};
+use crate::{ParseResult, State};
+use std::future::{poll_fn, Future};
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// A source of bytes that may not be ready yet, in the style of
+/// `futures::io::AsyncRead` but without tying the crate to a runtime.
+pub trait AsyncRead {
+    /// Reads into `buf`, returning how many bytes were read (zero at the end
+    /// of the source), or `Pending` after arranging for `cx` to be woken.
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>>;
+}
+
+impl AsyncRead for &[u8] {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let n = buf.len().min(self.len());
+        buf[..n].copy_from_slice(&self[..n]);
+        *self = &self[n..];
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncRead for &mut R {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+/// A sequence of values that become available over time, in the style of
+/// `futures::Stream`.
+pub trait Stream {
+    type Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+}
+
+/// Reads from a stream of byte chunks, such as the frames of a socket.
+pub struct StreamReader<S, B> {
+    stream: S,
+    chunk: Option<B>,
+    consumed: usize,
+}
+
+impl<S, B> StreamReader<S, B> {
+    pub fn new(stream: S) -> Self {
+        StreamReader {
+            stream,
+            chunk: None,
+            consumed: 0,
+        }
+    }
+}
+
+impl<S, B> AsyncRead for StreamReader<S, B>
+where
+    S: Stream<Item = io::Result<B>> + Unpin,
+    B: AsRef<[u8]> + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        loop {
+            if let Some(chunk) = &this.chunk {
+                let rest = &chunk.as_ref()[this.consumed..];
+                if !rest.is_empty() {
+                    let n = buf.len().min(rest.len());
+                    buf[..n].copy_from_slice(&rest[..n]);
+                    this.consumed += n;
+                    return Poll::Ready(Ok(n));
+                }
+            }
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.chunk = Some(chunk);
+                    this.consumed = 0;
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                Poll::Ready(None) => return Poll::Ready(Ok(0)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Runs `future` to completion on the current thread, polling it in a loop.
+/// Enough for tests and small tools; it spins rather than sleeping while the
+/// future is pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(v) = future.as_mut().poll(&mut cx) {
+            return v;
+        }
+    }
+}
+
+/// The async counterpart of `Reader`: drives parsers over an `AsyncRead`
+/// source, handing back each value as soon as the bytes for it have arrived.
+pub struct AsyncReader<R, E = Text> {
+    inner: R,
+    buffer: Buffer,
+    encoding: PhantomData<E>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    pub fn text(inner: R) -> Self {
+        AsyncReader::new(inner)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R, Bytes> {
+    pub fn bytes(inner: R) -> Self {
+        AsyncReader::new(inner)
+    }
+}
+
+impl<R: AsyncRead + Unpin, E: Encoding> AsyncReader<R, E> {
+    pub fn new(inner: R) -> Self {
+        AsyncReader {
+            inner,
+            buffer: Buffer::new(),
+            encoding: PhantomData,
+        }
+    }
+
+    /// Offset in the source of the first byte not yet consumed.
+    pub fn offset(&self) -> usize {
+        self.buffer.offset()
+    }
+
+    /// Runs `parse` once over the rest of the source.
+    pub async fn parse<T, F>(&mut self, mut parse: F) -> Result<T, ReadError>
+    where
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        poll_fn(|cx| self.poll_step(cx, &mut parse)).await
+    }
+
+    /// A stream of the values `parse` finds one after another in the source,
+    /// ending when the source does or after the first error. As with
+    /// `Reader::records`, a record that consumes nothing is an error.
+    pub fn records<T, F>(&mut self, parse: F) -> Records<'_, R, E, F>
+    where
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        Records {
+            reader: self,
+            parse,
+            failed: false,
+        }
+    }
+
+    fn poll_step<T, F>(&mut self, cx: &mut Context<'_>, parse: &mut F) -> Poll<Result<T, ReadError>>
+    where
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        loop {
+            if self.buffer.wanted() > 0 {
+                if let Err(e) = std::task::ready!(self.poll_fill(cx)) {
+                    return Poll::Ready(Err(e.into()));
+                }
+            }
+            if let Step::Done(v) = self.buffer.run::<E, _, _>(parse)? {
+                return Poll::Ready(Ok(v));
+            }
+        }
+    }
+
+    /// Whether every byte of the source has been read and consumed.
+    fn poll_at_end(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        if self.buffer.is_empty() && !self.buffer.is_eof() {
+            self.buffer.want(1);
+            if let Err(e) = std::task::ready!(self.poll_fill(cx)) {
+                return Poll::Ready(Err(e));
+            }
+        }
+        Poll::Ready(Ok(self.buffer.is_empty() && self.buffer.is_eof()))
+    }
+
+    /// Reads until the buffer has what it wants or the source runs dry. What
+    /// was read before a `Pending` stays buffered, and the parser only runs
+    /// again once the rest has arrived.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.buffer.wanted() > 0 {
+            let spare = self.buffer.spare();
+            match Pin::new(&mut self.inner).poll_read(cx, spare) {
+                Poll::Ready(Ok(n)) => self.buffer.filled(n),
+                Poll::Ready(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Stream of the records of an `AsyncReader`, see `AsyncReader::records`.
+pub struct Records<'r, R, E, F> {
+    reader: &'r mut AsyncReader<R, E>,
+    parse: F,
+    failed: bool,
+}
+
+impl<R, E, T, F> Records<'_, R, E, F>
+where
+    R: AsyncRead + Unpin,
+    E: Encoding,
+    F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T> + Unpin,
+{
+    pub async fn next(&mut self) -> Option<Result<T, ReadError>> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<R, E, T, F> Stream for Records<'_, R, E, F>
+where
+    R: AsyncRead + Unpin,
+    E: Encoding,
+    F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T> + Unpin,
+{
+    type Item = Result<T, ReadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.failed {
+            return Poll::Ready(None);
+        }
+        let result = match std::task::ready!(this.reader.poll_at_end(cx)) {
+            Ok(true) => return Poll::Ready(None),
+            Ok(false) => {
+                let start = this.reader.offset();
+                let result = std::task::ready!(this.reader.poll_step(cx, &mut this.parse));
+                result.and_then(|v| this.reader.buffer.advanced(start, v))
+            }
+            Err(e) => Err(e.into()),
+        };
+        this.failed = result.is_err();
+        Poll::Ready(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::*;
+    use crate::reader::CHUNK;
+    use crate::Parser;
+    use std::collections::VecDeque;
+
+    /// Hands out its chunks one at a time, not ready before each of them.
+    struct Chunks<B> {
+        chunks: VecDeque<B>,
+        ready: bool,
+    }
+
+    impl<B: Unpin> Stream for Chunks<B> {
+        type Item = io::Result<B>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(self.chunks.pop_front().map(Ok))
+        }
+    }
+
+    #[test]
+    fn test_async_records() {
+        let chunks = Chunks {
+            chunks: VecDeque::from(["1", "2,3", "4", ",", "", "56,"]),
+            ready: false,
+        };
+        let mut reader = AsyncReader::text(StreamReader::new(chunks));
+        let mut numbers = reader.records(|s| {
+            digit()
+                .many1()
+                .and_then(character(','))
+                .map(|(ds, _)| ds.iter().fold(0, |n, &d| n * 10 + d as u32))
+                .parse_at(s)
+        });
+        assert_eq!(Some(12), block_on(numbers.next()).map(Result::unwrap));
+        assert_eq!(Some(34), block_on(numbers.next()).map(Result::unwrap));
+        assert_eq!(Some(56), block_on(numbers.next()).map(Result::unwrap));
+        assert!(block_on(numbers.next()).is_none());
+    }
+
+    #[test]
+    fn test_async_large_value() {
+        let chunks = Chunks {
+            chunks: (0..64).map(|_| "7".repeat(CHUNK)).collect(),
+            ready: false,
+        };
+        let mut reader = AsyncReader::text(StreamReader::new(chunks));
+        let mut runs = 0;
+        let (digits, _) = block_on(reader.parse(|s| {
+            runs += 1;
+            digit().skip().many1().and_then(end()).parse_at(s)
+        }))
+        .unwrap();
+        assert_eq!(64 * CHUNK, digits.len());
+        // Neither every chunk nor every wake-up should start the parse over.
+        assert!(runs <= 10, "parsed {} times", runs);
+    }
+
+    #[test]
+    fn test_async_empty_record() {
+        let chunks = Chunks {
+            chunks: VecDeque::from(["1", "2a", "bc"]),
+            ready: false,
+        };
+        let mut reader = AsyncReader::text(StreamReader::new(chunks));
+        let mut numbers = reader.records(|s| {
+            take_while(char::is_ascii_digit)
+                .map(str::to_string)
+                .parse_at(s)
+        });
+        assert_eq!("12", block_on(numbers.next()).unwrap().unwrap());
+        let err = block_on(numbers.next()).unwrap().unwrap_err();
+        assert!(matches!(err, ReadError::Empty(2)));
+        assert!(block_on(numbers.next()).is_none());
+    }
+
+    #[test]
+    fn test_async_parse() {
+        let mut reader = AsyncReader::text("ab".as_bytes());
+        let err = block_on(reader.parse(|s| token("abc").parse_at(s))).unwrap_err();
+        assert!(matches!(err, ReadError::Parse(e) if e.offset() == 0));
+    }
+}
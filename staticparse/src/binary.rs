@@ -0,0 +1,103 @@
+use crate::helper::take;
+use crate::{Expected, Input, ParseError, ParseResult, Parser, State};
+use std::marker::PhantomData;
+
+macro_rules! numbers {
+    ($($be:ident, $le:ident => $t:ty;)*) => {
+        $(
+            pub fn $be<'a>() -> impl Parser<&'a [u8], $t> + Clone {
+                take(std::mem::size_of::<$t>())
+                    .map(|bytes: &[u8]| <$t>::from_be_bytes(bytes.try_into().unwrap()))
+                    .label(concat!("big-endian ", stringify!($t)))
+            }
+
+            pub fn $le<'a>() -> impl Parser<&'a [u8], $t> + Clone {
+                take(std::mem::size_of::<$t>())
+                    .map(|bytes: &[u8]| <$t>::from_le_bytes(bytes.try_into().unwrap()))
+                    .label(concat!("little-endian ", stringify!($t)))
+            }
+        )*
+    };
+}
+
+numbers! {
+    be_u8, le_u8 => u8;
+    be_i8, le_i8 => i8;
+    be_u16, le_u16 => u16;
+    be_i16, le_i16 => i16;
+    be_u32, le_u32 => u32;
+    be_i32, le_i32 => i32;
+    be_u64, le_u64 => u64;
+    be_i64, le_i64 => i64;
+    be_u128, le_u128 => u128;
+    be_i128, le_i128 => i128;
+    be_f32, le_f32 => f32;
+    be_f64, le_f64 => f64;
+}
+
+#[derive(Clone)]
+pub struct LengthData<P, N> {
+    length: P,
+    _marker: PhantomData<N>,
+}
+
+impl<I, P, N> Parser<I, I> for LengthData<P, N>
+where
+    I: Input,
+    P: Parser<I, N>,
+    N: TryInto<usize>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I> {
+        let offset = s.offset();
+        let (n, r) = self.length.parse_at(s)?;
+        let count = n
+            .try_into()
+            .map_err(|_| ParseError::new(offset, vec![Expected::Label("length")]))?;
+        take(count).parse_at(r)
+    }
+}
+
+/// Parses a length with `length`, then that many items, returning them as a
+/// slice of the input: `length_data(be_u32())` reads a u32-prefixed field.
+pub fn length_data<I, P, N>(length: P) -> impl Parser<I, I> + Clone
+where
+    I: Input,
+    P: Parser<I, N> + Clone,
+    N: TryInto<usize> + Clone,
+{
+    LengthData {
+        length,
+        _marker: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::binary::*;
+    use crate::helper::tag;
+
+    #[test]
+    fn test_parse_numbers() {
+        let input: &[u8] = &[0x01, 0x02, 0x03, 0x04, 0x05];
+        let (v, r) = be_u16().and_then(le_u16()).parse(input).unwrap();
+        assert_eq!((0x0102, 0x0403), v);
+        assert_eq!(&[0x05], r);
+        let input = 1.5_f64.to_le_bytes();
+        let (v, _) = le_f64().parse(&input[..]).unwrap();
+        assert_eq!(1.5, v);
+        let input = (-2_i64).to_be_bytes();
+        let (v, _) = be_i64().parse(&input[..]).unwrap();
+        assert_eq!(-2, v);
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        let input: &[u8] = b"FR\x00\x00\x00\x03abcdef";
+        let frame = tag(&b"FR"[..]).and_then(length_data(be_u32()));
+        let ((_, payload), r) = frame.parse(input).unwrap();
+        assert_eq!(b"abc", payload);
+        assert_eq!(b"def", r);
+        let err = be_u32().parse(&input[..3]).unwrap_err();
+        assert_eq!(&[Expected::Label("big-endian u32")], err.expected());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::sealed::Sealed;
+use crate::{CharSet, Expected, Input, ParseError, ParseResult, Parser, State};
+use parsecore::Member;
+
+impl From<Member> for Expected {
+    fn from(member: Member) -> Self {
+        match member {
+            Member::Char(c) => Expected::Char(c),
+            Member::Range(lo, hi) => Expected::Range(lo, hi),
+            Member::Category(category) => Expected::Label(category.name()),
+        }
+    }
+}
+
+/// What a failure to match `set` reports.
+fn expected(set: &CharSet) -> Vec<Expected> {
+    set.expected().iter().map(|&member| member.into()).collect()
+}
+
+/// Parses any one character in the set.
+impl<I: Input<Item = char>> Parser<I, char> for CharSet {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, char> {
+        match s.next_item() {
+            Some((c, r)) if self.contains(c) => Ok((c, r)),
+            Some(_) => Err(ParseError::new(s.offset(), expected(self))),
+            None => Err(s.exhausted(1, expected(self))),
+        }
+    }
+
+    fn char_set(&self, _: Sealed) -> Option<CharSet> {
+        Some(self.clone())
+    }
+
+    fn parse_set<'s>(&self, set: &CharSet, s: State<'s, I>, _: Sealed) -> ParseResult<'s, I, char> {
+        set.parse_at(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::*;
+    use crate::unicode::{general_category, GeneralCategory};
+    use crate::Category;
+    use std::ops::Bound;
+
+    #[test]
+    fn test_char_set() {
+        let set = CharSet::new()
+            .chars("_$")
+            .range('0'..='9')
+            .range('α'..='ω')
+            .range('β'..='ψ')
+            .category(Category::Uppercase);
+        assert!(set.contains('$') && set.contains('7') && set.contains('Q'));
+        assert!(set.contains('γ') && set.contains('Ж'));
+        assert!(!set.contains('a') && !set.contains('ж') && !set.contains('€'));
+        assert!(!set.clone().negate().contains('7'));
+        assert_eq!(Ok(('γ', "!")), set.parse("γ!"));
+        let err = set.parse("a").unwrap_err();
+        assert_eq!(Expected::Range('0', '9'), err.expected()[2]);
+    }
+
+    #[test]
+    fn test_excluded_bounds() {
+        let below = character_range('a'..'\u{e000}');
+        assert_eq!(Ok(('\u{d7ff}', "")), below.parse("\u{d7ff}"));
+        assert!(below.parse("\u{e000}").is_err());
+        let above = character_range((Bound::Excluded('\u{d7ff}'), Bound::Unbounded));
+        assert_eq!(Ok(('\u{e000}', "")), above.parse("\u{e000}"));
+        assert!(above.parse("\u{d7ff}").is_err());
+        let last = character_range((Bound::Excluded(char::MAX), Bound::Unbounded));
+        assert!(last.parse("\u{10ffff}").is_err());
+    }
+
+    #[test]
+    fn test_empty_range() {
+        let nothing = character_range(..'\0');
+        assert!(nothing.parse("\0").is_err() && nothing.parse("a").is_err());
+        assert!(character_range('b'..'b').parse("b").is_err());
+        assert!(CharSet::new().range('z'..='a').negate().contains('m'));
+    }
+
+    #[test]
+    fn test_one_of() {
+        let escape = one_of("\"\\/bfnrt");
+        assert_eq!(Ok(('n', "")), escape.parse("n"));
+        assert_eq!(
+            &[Expected::Char('"'), Expected::Char('\\')],
+            &escape.parse("q").unwrap_err().expected()[..2]
+        );
+        let plain = none_of("\"\\").many();
+        assert_eq!(Ok((vec!['a', 'é'], "\"")), plain.parse("aé\""));
+    }
+
+    #[test]
+    fn test_merged_or_else() {
+        let escape = character('n')
+            .or_else(character('t'))
+            .or_else(character_range('0'..='7'))
+            .or_else(character('é'));
+        let set = Parser::<&str, char>::char_set(&escape, Sealed).unwrap();
+        assert!(set.contains('t') && set.contains('5') && set.contains('é'));
+        assert_eq!(Ok(('5', "")), escape.parse("5"));
+        assert_eq!(
+            &[
+                Expected::Char('n'),
+                Expected::Char('t'),
+                Expected::Range('0', '7'),
+                Expected::Char('é')
+            ],
+            escape.parse("x").unwrap_err().expected()
+        );
+        let mixed = character('a').or_else(tag("bc").map(|_| 'b'));
+        assert!(Parser::<&str, char>::char_set(&mixed, Sealed).is_none());
+        assert_eq!(Ok(('b', "")), mixed.parse("bc"));
+    }
+
+    #[test]
+    fn test_category_version() {
+        // A modifier letter added in Unicode 15, newer than the tables.
+        let c = '\u{1e030}';
+        assert_eq!(GeneralCategory::Unassigned, general_category(c));
+        assert!(!Category::Letter.contains(c) && !Category::Alphabetic.contains(c));
+        assert!(!Category::Lowercase.contains(c));
+        assert!((char::MIN..=char::MAX)
+            .filter(|&c| Category::Letter.contains(c))
+            .all(|c| Category::Alphabetic.contains(c) && Category::Alphanumeric.contains(c)));
+    }
+}
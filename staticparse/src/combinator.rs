@@ -1,5 +1,10 @@
-use crate::Parser;
+use crate::error::{Expected, ParseError};
+use crate::sealed::Sealed;
+use crate::{CharSet, Input, ParseResult, Parser, Spanned, State};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct AndThen<P1, P2> {
@@ -7,15 +12,15 @@ pub struct AndThen<P1, P2> {
     pub(crate) parser2: P2,
 }
 
-impl<P1, P2, T, U> Parser<(T, U)> for AndThen<P1, P2>
+impl<I: Input, P1, P2, T, U> Parser<I, (T, U)> for AndThen<P1, P2>
 where
-    P1: Parser<T>,
-    P2: Parser<U>,
+    P1: Parser<I, T>,
+    P2: Parser<I, U>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<((T, U), &'a str)> {
-        self.parser1
-            .parse(s)
-            .and_then(|(v1, r1)| self.parser2.parse(r1).map(|(v2, r2)| ((v1, v2), r2)))
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, (T, U)> {
+        let (v1, r1) = self.parser1.parse_at(s)?;
+        let (v2, r2) = self.parser2.parse_at(r1.clone()).map_err(|e| r1.fail(e))?;
+        Ok(((v1, v2), r2))
     }
 }
 
@@ -23,18 +28,38 @@ where
 pub struct OrElse<P1, P2> {
     pub(crate) parser1: P1,
     pub(crate) parser2: P2,
+    /// Both alternatives' characters, when they're plain character sets.
+    pub(crate) merged: Option<CharSet>,
 }
 
-impl<P1, P2, T> Parser<T> for OrElse<P1, P2>
+impl<I: Input, P1, P2, T> Parser<I, T> for OrElse<P1, P2>
 where
-    P1: Parser<T>,
-    P2: Parser<T>,
+    P1: Parser<I, T>,
+    P2: Parser<I, T>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(T, &'a str)> {
-        match self.parser1.parse(s) {
-            r @ Some(_) => r,
-            _ => self.parser2.parse(s),
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        if let Some(set) = &self.merged {
+            return self.parser1.parse_set(set, s, Sealed);
         }
+        match self.parser1.parse_at(s.clone()) {
+            r @ Ok(_) => r,
+            Err(e1) if e1.is_fatal() => Err(e1),
+            Err(e1) => match self.parser2.parse_at(s.clone()) {
+                // Nothing consumed, so the first alternative's failure is
+                // still a possible way forward.
+                Ok((v, r)) if r.offset() == s.offset() => Ok((v, r.backtrack(e1)?)),
+                Ok(r) => Ok(r),
+                Err(e2) => Err(s.abandon(e1).merge(e2)),
+            },
+        }
+    }
+
+    fn char_set(&self, _: Sealed) -> Option<CharSet> {
+        self.merged.clone()
+    }
+
+    fn parse_set<'s>(&self, set: &CharSet, s: State<'s, I>, _: Sealed) -> ParseResult<'s, I, T> {
+        self.parser1.parse_set(set, s, Sealed)
     }
 }
 
@@ -42,15 +67,15 @@ where
 pub struct Optional<P> {
     pub(crate) parser: P,
 }
-impl<P, T> Parser<Option<T>> for Optional<P>
+impl<I: Input, P, T> Parser<I, Option<T>> for Optional<P>
 where
-    P: Parser<T>,
+    P: Parser<I, T>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(Option<T>, &'a str)> {
-        self.parser
-            .parse(s)
-            .map(|(v, r)| (Some(v), r))
-            .or_else(|| Some((None, s)))
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, Option<T>> {
+        match self.parser.parse_at(s.clone()) {
+            Ok((v, r)) => Ok((Some(v), r)),
+            Err(e) => Ok((None, s.backtrack(e)?)),
+        }
     }
 }
 
@@ -60,13 +85,13 @@ pub struct Map<P, F, T> {
     pub(crate) func: F,
     pub(crate) _marker: PhantomData<T>,
 }
-impl<P, F, T, U> Parser<U> for Map<P, F, T>
+impl<I: Input, P, F, T, U> Parser<I, U> for Map<P, F, T>
 where
-    P: Parser<T>,
+    P: Parser<I, T>,
     F: Fn(T) -> U,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(U, &'a str)> {
-        self.parser.parse(s).map(|(v, r)| ((self.func)(v), r))
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, U> {
+        self.parser.parse_at(s).map(|(v, r)| ((self.func)(v), r))
     }
 }
 
@@ -75,13 +100,19 @@ pub struct Filter<P, F> {
     pub(crate) parser: P,
     pub(crate) pred: F,
 }
-impl<P, F, T> Parser<T> for Filter<P, F>
+impl<I: Input, P, F, T> Parser<I, T> for Filter<P, F>
 where
-    P: Parser<T>,
+    P: Parser<I, T>,
     F: Fn(&T) -> bool,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(T, &'a str)> {
-        self.parser.parse(s).filter(|(v, _)| (self.pred)(v))
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        let (v, r) = self.parser.parse_at(s)?;
+        if (self.pred)(&v) {
+            Ok((v, r))
+        } else {
+            Err(ParseError::unexpected(offset))
+        }
     }
 }
 
@@ -89,18 +120,22 @@ where
 pub struct Many<P> {
     pub(crate) parser: P,
 }
-impl<P, T> Parser<Vec<T>> for Many<P>
+impl<I: Input, P, T> Parser<I, Vec<T>> for Many<P>
 where
-    P: Parser<T>,
+    P: Parser<I, T>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(Vec<T>, &'a str)> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, Vec<T>> {
         let mut input = s;
         let mut vs = Vec::new();
-        while let Some((v, r)) = self.parser.parse(input) {
-            vs.push(v);
-            input = r;
+        loop {
+            match self.parser.parse_at(input.clone()) {
+                Ok((v, r)) => {
+                    vs.push(v);
+                    input = r;
+                }
+                Err(e) => return Ok((vs, input.backtrack(e)?)),
+            }
         }
-        Some((vs, input))
     }
 }
 
@@ -108,16 +143,22 @@ where
 pub struct Many1<P> {
     pub(crate) parser: P,
 }
-impl<P, T> Parser<Vec<T>> for Many1<P>
+impl<I: Input, P, T> Parser<I, Vec<T>> for Many1<P>
 where
-    P: Parser<T> + Clone,
+    P: Parser<I, T>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(Vec<T>, &'a str)> {
-        self.parser
-            .clone()
-            .many()
-            .filter(|vs| !vs.is_empty())
-            .parse(s)
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, Vec<T>> {
+        let (v, mut input) = self.parser.parse_at(s)?;
+        let mut vs = vec![v];
+        loop {
+            match self.parser.parse_at(input.clone()) {
+                Ok((v, r)) => {
+                    vs.push(v);
+                    input = r;
+                }
+                Err(e) => return Ok((vs, input.backtrack(e)?)),
+            }
+        }
     }
 }
 
@@ -126,12 +167,12 @@ pub struct Skip<P, T> {
     pub(crate) parser: P,
     pub(crate) _marker: PhantomData<T>,
 }
-impl<P, T> Parser<()> for Skip<P, T>
+impl<I: Input, P, T> Parser<I, ()> for Skip<P, T>
 where
-    P: Parser<T> + Clone,
+    P: Parser<I, T> + Clone,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<((), &'a str)> {
-        self.parser.clone().map(|_| ()).parse(s)
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, ()> {
+        self.parser.clone().map(|_| ()).parse_at(s)
     }
 }
 
@@ -140,18 +181,19 @@ pub struct Except<P1, P2> {
     pub(crate) parser1: P1,
     pub(crate) parser2: P2,
 }
-impl<P1, P2, T> Parser<T> for Except<P1, P2>
+impl<I: Input, P1, P2, T> Parser<I, T> for Except<P1, P2>
 where
     T: PartialEq,
-    P1: Parser<T>,
-    P2: Parser<T>,
+    P1: Parser<I, T>,
+    P2: Parser<I, T>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(T, &'a str)> {
-        let (v1, r) = self.parser1.parse(s)?;
-        if self.parser2.parse(s).map_or(false, |(v2, _)| v1 == v2) {
-            return None;
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let (v1, r) = self.parser1.parse_at(s.clone())?;
+        match self.parser2.parse_at(s.clone()) {
+            Ok((v2, _)) if v1 == v2 => Err(ParseError::unexpected(s.offset())),
+            Err(e) if e.is_incomplete() => Err(e),
+            _ => Ok((v1, r)),
         }
-        Some((v1, r))
     }
 }
 
@@ -161,32 +203,106 @@ pub struct SepBy<P1, P2, U> {
     pub(crate) sep: P2,
     pub(crate) _marker: PhantomData<U>,
 }
-impl<P1, P2, T, U> Parser<Vec<T>> for SepBy<P1, P2, U>
+impl<I: Input, P1, P2, T, U> Parser<I, Vec<T>> for SepBy<P1, P2, U>
 where
-    P1: Parser<T>,
-    P2: Parser<U>,
+    P1: Parser<I, T>,
+    P2: Parser<I, U>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(Vec<T>, &'a str)> {
-        let mut vs = Vec::new();
-        let mut input = s;
-
-        if let Some((v, r)) = self.parser.parse(input) {
-            vs.push(v);
-            input = r;
-        } else {
-            return None;
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, Vec<T>> {
+        let (v, mut input) = self.parser.parse_at(s)?;
+        let mut vs = vec![v];
+        loop {
+            // A separator with no element after it is left unconsumed, and
+            // whichever of the two failed is what could have come next.
+            let element = self
+                .sep
+                .parse_at(input.clone())
+                .and_then(|(_, r1)| self.parser.parse_at(r1.clone()).map_err(|e| r1.fail(e)));
+            match element {
+                Ok((v, r)) => {
+                    vs.push(v);
+                    input = r;
+                }
+                Err(e) => return Ok((vs, input.backtrack(e)?)),
+            }
         }
+    }
+}
 
-        while let Some((_, r1)) = self.sep.parse(input) {
-            if let Some((v, r2)) = self.parser.parse(r1) {
-                vs.push(v);
-                input = r2;
-            } else {
-                break;
+#[derive(Clone)]
+pub struct ChainLeft<P1, P2, F> {
+    pub(crate) parser: P1,
+    pub(crate) op: P2,
+    pub(crate) _marker: PhantomData<F>,
+}
+impl<I: Input, P1, P2, T, F> Parser<I, T> for ChainLeft<P1, P2, F>
+where
+    P1: Parser<I, T>,
+    P2: Parser<I, F>,
+    F: Fn(T, T) -> T,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let (mut acc, mut input) = self.parser.parse_at(s)?;
+        loop {
+            match operation(&self.op, &self.parser, input.clone()) {
+                Ok(((f, v), r)) => {
+                    acc = f(acc, v);
+                    input = r;
+                }
+                Err(e) => return Ok((acc, input.backtrack(e)?)),
             }
         }
+    }
+}
 
-        Some((vs, input))
+/// An operator and the operand after it, for `ChainLeft` and `ChainRight`.
+fn operation<'s, I, P1, P2, T, F>(
+    op: &P2,
+    parser: &P1,
+    s: State<'s, I>,
+) -> ParseResult<'s, I, (F, T)>
+where
+    I: Input,
+    P1: Parser<I, T>,
+    P2: Parser<I, F>,
+{
+    let (f, r) = op.parse_at(s)?;
+    let (v, r) = parser.parse_at(r.clone()).map_err(|e| r.fail(e))?;
+    Ok(((f, v), r))
+}
+
+#[derive(Clone)]
+pub struct ChainRight<P1, P2, F> {
+    pub(crate) parser: P1,
+    pub(crate) op: P2,
+    pub(crate) _marker: PhantomData<F>,
+}
+impl<I: Input, P1, P2, T, F> Parser<I, T> for ChainRight<P1, P2, F>
+where
+    P1: Parser<I, T>,
+    P2: Parser<I, F>,
+    F: Fn(T, T) -> T,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let (mut last, mut input) = self.parser.parse_at(s)?;
+        let mut pending = Vec::new();
+        loop {
+            match operation(&self.op, &self.parser, input.clone()) {
+                Ok(((f, v), r)) => {
+                    pending.push((std::mem::replace(&mut last, v), f));
+                    input = r;
+                }
+                Err(e) => {
+                    input = input.backtrack(e)?;
+                    break;
+                }
+            }
+        }
+        let folded = pending
+            .into_iter()
+            .rev()
+            .fold(last, |acc, (v, f)| f(v, acc));
+        Ok((folded, input))
     }
 }
 
@@ -197,16 +313,267 @@ pub struct Between<P1, P2, P3, U, V> {
     pub(crate) end: P3,
     pub(crate) _marker: PhantomData<(U, V)>,
 }
-impl<P1, P2, P3, T, U, V> Parser<T> for Between<P1, P2, P3, U, V>
+impl<I: Input, P1, P2, P3, T, U, V> Parser<I, T> for Between<P1, P2, P3, U, V>
+where
+    P1: Parser<I, T>,
+    P2: Parser<I, U>,
+    P3: Parser<I, V>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let (_, r) = self.start.parse_at(s)?;
+        let (v, r) = self.parser.parse_at(r.clone()).map_err(|e| r.fail(e))?;
+        let (_, r) = self.end.parse_at(r.clone()).map_err(|e| r.fail(e))?;
+        Ok((v, r))
+    }
+}
+
+#[derive(Clone)]
+pub struct Expect<P> {
+    pub(crate) parser: P,
+    pub(crate) expected: Expected,
+}
+impl<I: Input, P, T> Parser<I, T> for Expect<P>
+where
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        match self.parser.parse_at(s) {
+            Ok((v, r)) => Ok((v, r.relabel(offset, &self.expected))),
+            Err(e) if e.offset() == offset => Err(e.expecting(self.expected.clone())),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Label<P> {
+    pub(crate) parser: P,
+    pub(crate) name: &'static str,
+}
+impl<I: Input, P, T> Parser<I, T> for Label<P>
 where
-    P1: Parser<T>,
-    P2: Parser<U>,
-    P3: Parser<V>,
+    P: Parser<I, T>,
 {
-    fn parse<'a>(&self, s: &'a str) -> Option<(T, &'a str)> {
-        let (_, r) = self.start.parse(s)?;
-        let (v, r) = self.parser.parse(r)?;
-        let (_, r) = self.end.parse(r)?;
-        Some((v, r))
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        let expected = Expected::Label(self.name);
+        match self.parser.parse_at(s) {
+            Ok((v, r)) if r.offset() > offset => Ok((v, r.after(self.name))),
+            Ok((v, r)) => Ok((v, r.relabel(offset, &expected))),
+            Err(e) if e.offset() == offset => Err(e.expecting(expected)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Cut<P> {
+    pub(crate) parser: P,
+}
+impl<I: Input, P, T> Parser<I, T> for Cut<P>
+where
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        self.parser.parse_at(s).map_err(ParseError::commit)
+    }
+}
+
+#[derive(Clone)]
+pub struct Context<P> {
+    pub(crate) parser: P,
+    pub(crate) name: &'static str,
+}
+impl<I: Input, P, T> Parser<I, T> for Context<P>
+where
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        self.parser.parse_at(s).map_err(|e| {
+            if e.offset() > offset {
+                e.within(self.name)
+            } else {
+                e
+            }
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct RecoverWith<P1, P2> {
+    pub(crate) parser: P1,
+    pub(crate) strategy: P2,
+}
+impl<I: Input, P1, P2, T> Parser<I, T> for RecoverWith<P1, P2>
+where
+    P1: Parser<I, T>,
+    P2: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        match self.parser.parse_at(s.clone()) {
+            Err(e) if s.is_recovering() && !e.is_incomplete() => {
+                let e = s.fail(e);
+                let at = s.clone().advance(e.offset().saturating_sub(s.offset()));
+                match self.strategy.parse_at(at) {
+                    Ok((v, r)) => Ok((v, r.record(e))),
+                    Err(_) => Err(e),
+                }
+            }
+            r => r,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Recognize<P, T> {
+    pub(crate) parser: P,
+    pub(crate) _marker: PhantomData<T>,
+}
+impl<I: Input, P, T> Parser<I, I> for Recognize<P, T>
+where
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I> {
+        let (src, offset) = (s.source(), s.offset());
+        let (_, r) = self.parser.parse_at(s)?;
+        Ok((src.slice(offset, r.offset()), r))
+    }
+}
+
+#[derive(Clone)]
+pub struct Consumed<P> {
+    pub(crate) parser: P,
+}
+impl<I: Input, P, T> Parser<I, (I, T)> for Consumed<P>
+where
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, (I, T)> {
+        let (src, offset) = (s.source(), s.offset());
+        let (v, r) = self.parser.parse_at(s)?;
+        Ok(((src.slice(offset, r.offset()), v), r))
+    }
+}
+
+#[derive(Clone)]
+pub struct Spanning<P> {
+    pub(crate) parser: P,
+}
+impl<I: Input, P, T> Parser<I, Spanned<T>> for Spanning<P>
+where
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, Spanned<T>> {
+        let (line, column) = s.line_column();
+        let offset = s.offset();
+        let (value, r) = self.parser.parse_at(s)?;
+        let spanned = Spanned {
+            value,
+            start: offset,
+            end: r.offset(),
+            line,
+            column,
+        };
+        Ok((spanned, r))
+    }
+}
+
+/// What a `Memo` parser found at each offset during one parse run.
+pub(crate) struct MemoTable<T> {
+    run: usize,
+    entries: HashMap<usize, MemoEntry<T>>,
+}
+
+impl<T> MemoTable<T> {
+    pub(crate) fn new() -> Self {
+        MemoTable {
+            run: usize::MAX,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+/// `active` entries belong to a parse that's still running at that offset;
+/// reaching one again means the rule is left recursive there.
+struct MemoEntry<T> {
+    result: Result<(T, usize, Option<Rc<ParseError>>), ParseError>,
+    recovered: Vec<ParseError>,
+    active: bool,
+    recursed: bool,
+}
+
+impl<T: Clone> MemoEntry<T> {
+    fn new<'s, I: Input>(s: &State<'s, I>, result: &ParseResult<'s, I, T>, active: bool) -> Self {
+        let (result, recovered) = match result {
+            Ok((v, r)) => (Ok((v.clone(), r.offset(), r.hint())), r.recorded_since(s)),
+            Err(e) => (Err(e.clone()), Vec::new()),
+        };
+        MemoEntry {
+            result,
+            recovered,
+            active,
+            recursed: false,
+        }
+    }
+
+    fn replay<'s, I: Input>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        let replayed = self.recovered.iter().cloned().fold(s, State::record);
+        match &self.result {
+            Ok((v, end, hint)) => {
+                let r = replayed.advance(end - offset).with_hint(hint.clone());
+                Ok((v.clone(), r))
+            }
+            Err(e) => Err(e.clone()),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Memo<P, T> {
+    pub(crate) parser: P,
+    pub(crate) table: Rc<RefCell<MemoTable<T>>>,
+}
+impl<I: Input, P, T> Parser<I, T> for Memo<P, T>
+where
+    P: Parser<I, T>,
+    T: Clone,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        {
+            let mut table = self.table.borrow_mut();
+            if table.run != s.run() {
+                table.run = s.run();
+                table.entries.clear();
+            }
+            if let Some(entry) = table.entries.get_mut(&s.offset()) {
+                entry.recursed |= entry.active;
+                return entry.replay(s);
+            }
+            let seed = Err(ParseError::unexpected(s.offset()));
+            table
+                .entries
+                .insert(s.offset(), MemoEntry::new(&s, &seed, true));
+        }
+        let mut result = self.parser.parse_at(s.clone());
+        if self.table.borrow().entries[&s.offset()].recursed {
+            // Grow the seed: rerun the rule with its last result standing in
+            // for the recursive call until that stops getting any longer.
+            while let Ok((_, r)) = &result {
+                let end = r.offset();
+                let grown = MemoEntry::new(&s, &result, true);
+                self.table.borrow_mut().entries.insert(s.offset(), grown);
+                match self.parser.parse_at(s.clone()) {
+                    Ok((v, r)) if r.offset() > end => result = Ok((v, r)),
+                    Err(e) if e.is_fatal() => result = Err(e),
+                    _ => break,
+                }
+            }
+        }
+        let entry = MemoEntry::new(&s, &result, false);
+        self.table.borrow_mut().entries.insert(s.offset(), entry);
+        result
     }
 }
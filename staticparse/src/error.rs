@@ -0,0 +1,215 @@
+use parsecore::Diagnostic;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    Any,
+    Char(char),
+    Range(char, char),
+    Token(String),
+    Item(String),
+    Count(usize),
+    Label(&'static str),
+    End,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Any => write!(f, "any character"),
+            Expected::Char(c) => write!(f, "{:?}", c),
+            Expected::Range(lo, hi) => write!(f, "{:?}..={:?}", lo, hi),
+            Expected::Token(t) => write!(f, "{:?}", t),
+            Expected::Item(item) => write!(f, "{}", item),
+            Expected::Count(1) => write!(f, "1 item"),
+            Expected::Count(n) => write!(f, "{} items", n),
+            Expected::Label(name) => write!(f, "{}", name),
+            Expected::End => write!(f, "end of input"),
+        }
+    }
+}
+
+/// The reason a parser failed.
+///
+/// `offset` is the byte offset of the failure in the original input. When two
+/// errors are merged the one that got furthest into the input wins; errors at
+/// the same offset union their expectations.
+///
+/// `context` is the trail of rule names, outermost first, that the failure
+/// happened inside of. `after` names the labelled rule that ended right where
+/// the failure happened, if any.
+///
+/// A committed error comes from a parser that was past a `cut`: alternatives
+/// and repetitions propagate it instead of backtracking.
+///
+/// An incomplete error comes from a parser that ran off the end of a partial
+/// input and needs at least `needed` more items to decide. It wins over any
+/// other error, since more input might still make the parse succeed.
+///
+/// In recovery mode, `recorded` counts the recovered errors on the path that
+/// failed here; errors recorded in branches that were backtracked out of
+/// aren't among them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    offset: usize,
+    expected: Vec<Expected>,
+    context: Vec<&'static str>,
+    after: Option<&'static str>,
+    committed: bool,
+    needed: Option<usize>,
+    recorded: usize,
+}
+
+impl ParseError {
+    pub fn new(offset: usize, expected: Vec<Expected>) -> Self {
+        ParseError {
+            offset,
+            expected,
+            context: Vec::new(),
+            after: None,
+            committed: false,
+            needed: None,
+            recorded: 0,
+        }
+    }
+
+    pub fn incomplete(offset: usize, needed: usize) -> Self {
+        ParseError {
+            needed: Some(needed),
+            ..ParseError::new(offset, Vec::new())
+        }
+    }
+
+    pub fn unexpected(offset: usize) -> Self {
+        ParseError::new(offset, Vec::new())
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn expected(&self) -> &[Expected] {
+        &self.expected
+    }
+
+    pub fn context(&self) -> &[&'static str] {
+        &self.context
+    }
+
+    pub fn after(&self) -> Option<&'static str> {
+        self.after
+    }
+
+    pub fn is_committed(&self) -> bool {
+        self.committed
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.needed.is_some()
+    }
+
+    /// How many more items an incomplete error needs before parsing can go on.
+    pub fn needed(&self) -> Option<usize> {
+        self.needed
+    }
+
+    pub fn merge(mut self, mut other: ParseError) -> Self {
+        if self.is_incomplete() {
+            return self;
+        }
+        if other.is_incomplete() {
+            return other;
+        }
+        if other.offset > self.offset {
+            other.committed |= self.committed;
+            return other;
+        }
+        self.committed |= other.committed;
+        if other.offset == self.offset {
+            self.recorded = self.recorded.max(other.recorded);
+            for e in other.expected {
+                if !self.expected.contains(&e) {
+                    self.expected.push(e);
+                }
+            }
+            if other.context.len() > self.context.len() {
+                self.context = other.context;
+            }
+            self.after = self.after.or(other.after);
+        }
+        self
+    }
+
+    pub(crate) fn expecting(mut self, expected: Expected) -> Self {
+        self.expected = vec![expected];
+        self.context.clear();
+        self
+    }
+
+    /// Committed and incomplete errors can't be backtracked out of.
+    pub(crate) fn is_fatal(&self) -> bool {
+        self.committed || self.is_incomplete()
+    }
+
+    pub(crate) fn recorded(&self) -> usize {
+        self.recorded
+    }
+
+    pub(crate) fn after_recording(mut self, count: usize) -> Self {
+        self.recorded = count;
+        self
+    }
+
+    pub(crate) fn commit(mut self) -> Self {
+        self.committed = true;
+        self
+    }
+
+    /// Moves the error `by` further into the input, for parsers that only saw
+    /// a window of it.
+    pub(crate) fn shifted(mut self, by: usize) -> Self {
+        self.offset += by;
+        self
+    }
+
+    pub(crate) fn following(mut self, name: &'static str) -> Self {
+        self.after = Some(name);
+        self
+    }
+
+    pub(crate) fn within(mut self, name: &'static str) -> Self {
+        self.context.insert(0, name);
+        self
+    }
+
+    fn diagnostic(&self) -> Diagnostic<'_, Expected> {
+        Diagnostic {
+            offset: self.offset,
+            expected: &self.expected,
+            context: &self.context,
+            after: self.after,
+        }
+    }
+
+    /// Describes the failure in terms of what was found at `offset` in `src`.
+    pub fn message(&self, src: &str) -> String {
+        self.diagnostic().message(src)
+    }
+
+    /// Renders the error rustc-style: the message, the offending line of `src`
+    /// and a caret under the failing column.
+    pub fn render(&self, src: &str) -> String {
+        self.diagnostic().render(src)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(needed) = self.needed {
+            return write!(f, "need {} more at offset {}", needed, self.offset);
+        }
+        self.diagnostic().fmt(f)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -1,55 +1,709 @@
-use crate::Parser;
+use crate::combinator::{Expect, Filter};
+use crate::unicode::{is_xid_continue, GeneralCategory};
+use crate::{Category, CharSet, Expected, Input, ParseError, ParseResult, Parser, State};
+use std::cell::OnceCell;
+use std::marker::PhantomData;
 use std::ops::RangeBounds;
+use std::rc::{Rc, Weak};
+
+pub use self::AnyParser as CharParser;
+pub type TokenParser<'a> = TagParser<&'a str>;
+
+#[derive(Clone)]
+pub struct AnyParser;
+impl<I: Input> Parser<I, I::Item> for AnyParser {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I::Item> {
+        s.next_item()
+            .ok_or_else(|| s.exhausted(1, vec![Expected::Any]))
+    }
+}
+
+pub fn any<I: Input>() -> impl Parser<I, I::Item> + Clone {
+    AnyParser
+}
+
+pub fn item<I: Input>(item: I::Item) -> impl Parser<I, I::Item> + Clone {
+    let expected = I::expected_item(item.clone());
+    Expect {
+        parser: satisfy::<I, _>(move |parsed| *parsed == item),
+        expected,
+    }
+}
+
+pub fn satisfy<I, F>(pred: F) -> impl Parser<I, I::Item> + Clone
+where
+    I: Input,
+    F: Fn(&I::Item) -> bool + Clone,
+{
+    Filter {
+        parser: AnyParser,
+        pred,
+    }
+}
+
+#[derive(Clone)]
+pub struct TagParser<I> {
+    tag: I,
+}
+
+impl<I: Input> Parser<I, I> for TagParser<I> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I> {
+        let len = self.tag.input_len();
+        let available = s.rest().input_len();
+        if s.source().starts_with_at(s.offset(), self.tag) {
+            Ok((s.rest().slice(0, len), s.advance(len)))
+        } else if available < len && self.tag.starts_with_at(0, s.rest()) {
+            Err(s.exhausted(len - available, vec![self.tag.expected_tag()]))
+        } else {
+            Err(ParseError::new(s.offset(), vec![self.tag.expected_tag()]))
+        }
+    }
+}
+
+pub fn tag<I: Input>(tag: I) -> impl Parser<I, I> + Clone {
+    TagParser { tag }
+}
+
+#[derive(Clone)]
+pub struct TakeParser {
+    count: usize,
+}
+
+impl<I: Input> Parser<I, I> for TakeParser {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I> {
+        let mut r = s.clone();
+        for taken in 0..self.count {
+            match r.next_item() {
+                Some((_, next)) => r = next,
+                None => {
+                    return Err(s.exhausted(self.count - taken, vec![Expected::Count(self.count)]))
+                }
+            }
+        }
+        Ok((s.source().slice(s.offset(), r.offset()), r))
+    }
+}
+
+/// Parses exactly `count` items, returning them as a slice of the input.
+pub fn take<I: Input>(count: usize) -> impl Parser<I, I> + Clone {
+    TakeParser { count }
+}
+
+#[derive(Clone)]
+pub struct TakeWhile<F> {
+    min: usize,
+    max: usize,
+    pred: F,
+}
+
+impl<I, F> Parser<I, I> for TakeWhile<F>
+where
+    I: Input,
+    F: Fn(&I::Item) -> bool,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I> {
+        let (count, end) = s.source().scan_while(s.offset(), self.max, &self.pred);
+        let (src, offset) = (s.source(), s.offset());
+        let r = s.advance(end - offset);
+        // Out of input with room for more: the run may not be over yet.
+        if count < self.max && r.is_empty() && r.is_partial() {
+            let needed = self.min.saturating_sub(count).max(1);
+            return Err(ParseError::incomplete(r.offset(), needed));
+        }
+        if count < self.min {
+            return Err(ParseError::unexpected(r.offset()));
+        }
+        Ok((src.slice(offset, end), r))
+    }
+}
+
+/// The longest run of items satisfying `pred`, possibly empty, as a slice of
+/// the input.
+pub fn take_while<I, F>(pred: F) -> impl Parser<I, I> + Clone
+where
+    I: Input,
+    F: Fn(&I::Item) -> bool + Clone,
+{
+    take_while_m_n(0, usize::MAX, pred)
+}
+
+/// Like `take_while`, but fails unless at least one item matches.
+pub fn take_while1<I, F>(pred: F) -> impl Parser<I, I> + Clone
+where
+    I: Input,
+    F: Fn(&I::Item) -> bool + Clone,
+{
+    take_while_m_n(1, usize::MAX, pred)
+}
+
+/// Between `m` and `n` items satisfying `pred`, taking as many as it can.
+pub fn take_while_m_n<I, F>(m: usize, n: usize, pred: F) -> impl Parser<I, I> + Clone
+where
+    I: Input,
+    F: Fn(&I::Item) -> bool + Clone,
+{
+    TakeWhile {
+        min: m,
+        max: n,
+        pred,
+    }
+}
+
+/// The longest run of items up to the first one satisfying `pred`.
+pub fn take_till<I, F>(pred: F) -> impl Parser<I, I> + Clone
+where
+    I: Input,
+    F: Fn(&I::Item) -> bool + Clone,
+{
+    take_while(move |item: &I::Item| !pred(item))
+}
 
 #[derive(Clone)]
-pub struct CharParser;
-impl Parser<char> for CharParser {
-    fn parse<'a>(&self, s: &'a str) -> Option<(char, &'a str)> {
-        let mut chars = s.chars();
-        match chars.next() {
-            Some(c) => Some((c, chars.as_str())),
-            _ => None,
+pub struct TakeUntil<I> {
+    needle: I,
+}
+
+impl<I: Input> Parser<I, I> for TakeUntil<I> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, I> {
+        match s.source().find_at(s.offset(), self.needle) {
+            Some(at) => {
+                let offset = s.offset();
+                Ok((s.source().slice(offset, at), s.advance(at - offset)))
+            }
+            None => {
+                let len = s.rest().input_len();
+                let end = s.advance(len);
+                Err(end.exhausted(1, vec![self.needle.expected_tag()]))
+            }
         }
     }
 }
 
-pub fn digit() -> impl Parser<u8> + Clone {
-    CharParser
-        .filter(|parsed| parsed.is_ascii_digit())
-        .map(|c| c.to_digit(10).unwrap() as u8)
+/// Everything before the next occurrence of `needle`, which is left for the
+/// following parser; fails if `needle` never shows up.
+pub fn take_until<I: Input>(needle: I) -> impl Parser<I, I> + Clone {
+    TakeUntil { needle }
 }
 
-pub fn letter() -> impl Parser<char> + Clone {
-    CharParser.filter(|parsed| parsed.is_ascii_alphabetic())
+pub fn digit<I: Input<Item = char>>() -> impl Parser<I, u8> + Clone {
+    Expect {
+        parser: satisfy::<I, _>(|parsed| parsed.is_ascii_digit())
+            .map(|c| c.to_digit(10).unwrap() as u8),
+        expected: Expected::Label("digit"),
+    }
 }
 
-pub fn character(c: char) -> impl Parser<char> + Clone {
-    CharParser.filter(move |&parsed| parsed == c)
+pub fn letter<I: Input<Item = char>>() -> impl Parser<I, char> + Clone {
+    Expect {
+        parser: satisfy::<I, _>(|parsed| parsed.is_ascii_alphabetic()),
+        expected: Expected::Label("letter"),
+    }
 }
 
-pub fn character_range<R>(r: R) -> impl Parser<char> + Clone
+/// A letter in any script, unlike the ASCII-only `letter`.
+pub fn unicode_letter<I: Input<Item = char>>() -> impl Parser<I, char> + Clone {
+    CharSet::new().category(Category::Letter)
+}
+
+pub fn unicode_alphanumeric<I: Input<Item = char>>() -> impl Parser<I, char> + Clone {
+    CharSet::new().category(Category::Alphanumeric)
+}
+
+pub fn unicode_whitespace<I: Input<Item = char>>() -> impl Parser<I, char> + Clone {
+    CharSet::new().category(Category::Whitespace)
+}
+
+/// A character of the given general category.
+pub fn unicode_category<I>(category: GeneralCategory) -> impl Parser<I, char> + Clone
 where
+    I: Input<Item = char>,
+{
+    CharSet::new().category(Category::General(category))
+}
+
+/// An identifier as UAX #31 defines them: an XID_Start character followed by
+/// XID_Continue characters. Like most languages, a leading underscore is
+/// allowed too.
+pub fn identifier<I: Input<Item = char>>() -> impl Parser<I, I> + Clone {
+    CharSet::new()
+        .char('_')
+        .category(Category::XidStart)
+        .and_then(take_while(|c: &char| is_xid_continue(*c)))
+        .recognize()
+        .label("identifier")
+}
+
+pub fn character<I: Input<Item = char>>(c: char) -> impl Parser<I, char> + Clone {
+    CharSet::new().char(c)
+}
+
+pub fn character_range<I, R>(r: R) -> impl Parser<I, char> + Clone
+where
+    I: Input<Item = char>,
     R: RangeBounds<char> + Clone,
 {
-    CharParser.filter(move |parsed| r.contains(parsed))
+    CharSet::new().range(r)
+}
+
+/// Any one of `chars`, checked against a lookup table rather than one by
+/// one.
+pub fn one_of<I: Input<Item = char>>(chars: &str) -> impl Parser<I, char> + Clone {
+    CharSet::from(chars)
 }
 
+/// Any character but those in `chars`.
+pub fn none_of<I: Input<Item = char>>(chars: &str) -> impl Parser<I, char> + Clone {
+    CharSet::from(chars).negate()
+}
+
+/// Skips items until `stop` would succeed, without consuming what `stop`
+/// matches. Never fails; meant as a `recover_with` strategy.
 #[derive(Clone)]
-pub struct TokenParser<'b> {
-    token: &'b str,
+pub struct SkipUntil<P, T> {
+    stop: P,
+    _marker: PhantomData<T>,
 }
 
-impl<'b> Parser<String> for TokenParser<'b> {
-    fn parse<'a>(&self, s: &'a str) -> Option<(String, &'a str)> {
-        if let Some(r) = s.strip_prefix(self.token) {
-            let v = s[..self.token.len()].to_owned();
-            Some((v, r))
+impl<I, P, T> Parser<I, ()> for SkipUntil<P, T>
+where
+    I: Input,
+    P: Parser<I, T>,
+{
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, ()> {
+        let mut input = s;
+        loop {
+            match self.stop.parse_at(input.clone()) {
+                Ok(_) => break,
+                Err(e) if e.is_incomplete() => return Err(e),
+                Err(_) => {}
+            }
+            match input.next_item() {
+                Some((_, r)) => input = r,
+                None if input.is_partial() => return Err(input.exhausted(1, Vec::new())),
+                None => break,
+            }
+        }
+        Ok(((), input))
+    }
+}
+
+pub fn skip_until<I, P, T>(stop: P) -> impl Parser<I, ()> + Clone
+where
+    I: Input,
+    P: Parser<I, T> + Clone,
+    T: Clone,
+{
+    SkipUntil {
+        stop,
+        _marker: PhantomData,
+    }
+}
+
+/// Like `skip_until(one_of(stop))`, but steps over nested groups: `groups`
+/// lists opening and closing characters in pairs, as in `"[]{}"`, and a
+/// `stop` character inside a group opened along the way doesn't count. A
+/// closing character with no group open is skipped like any other, so the
+/// skip can start inside a group it never saw open. Never fails; meant as a
+/// `recover_with` strategy.
+#[derive(Clone)]
+pub struct SkipBalanced {
+    groups: Vec<(char, char)>,
+    stop: CharSet,
+}
+
+impl<I: Input<Item = char>> Parser<I, ()> for SkipBalanced {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, ()> {
+        let mut input = s;
+        let mut open: Vec<char> = Vec::new();
+        loop {
+            let Some((c, r)) = input.next_item() else {
+                if input.is_partial() {
+                    return Err(input.exhausted(1, Vec::new()));
+                }
+                break;
+            };
+            if open.is_empty() && self.stop.contains(c) {
+                break;
+            }
+            if let Some(&(_, close)) = self.groups.iter().find(|(start, _)| *start == c) {
+                open.push(close);
+            } else if let Some(i) = open.iter().rposition(|&close| close == c) {
+                open.truncate(i);
+            }
+            input = r;
+        }
+        Ok(((), input))
+    }
+}
+
+pub fn skip_balanced<I: Input<Item = char>>(
+    groups: &str,
+    stop: &str,
+) -> impl Parser<I, ()> + Clone {
+    let groups: Vec<char> = groups.chars().collect();
+    assert!(
+        groups.len().is_multiple_of(2),
+        "groups come in open/close pairs"
+    );
+    SkipBalanced {
+        groups: groups.chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+        stop: CharSet::from(stop),
+    }
+}
+
+#[derive(Clone)]
+pub struct EndParser;
+impl<I: Input> Parser<I, ()> for EndParser {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, ()> {
+        if !s.is_empty() {
+            Err(ParseError::new(s.offset(), vec![Expected::End]))
+        } else if s.is_partial() {
+            Err(ParseError::incomplete(s.offset(), 1))
         } else {
-            None
+            Ok(((), s))
         }
     }
 }
 
-pub fn token<'a>(s: &'a str) -> impl Parser<String> + Clone + 'a {
-    TokenParser { token: s }
+pub fn end<I: Input>() -> impl Parser<I, ()> + Clone {
+    EndParser
+}
+
+/// Object-safe face of `Parser`, so a rule can be stored behind a pointer
+/// before its concrete type is known.
+pub(crate) trait Rule<I: Input, T> {
+    fn run<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T>;
+}
+
+impl<I: Input, T, P: Parser<I, T>> Rule<I, T> for P {
+    fn run<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        self.parse_at(s)
+    }
+}
+
+type Slot<'p, I, T> = OnceCell<Box<dyn Rule<I, T> + 'p>>;
+
+/// A parser that refers to itself, see `recursive`.
+///
+/// The parser returned by `recursive` owns the rule; the handle given to its
+/// definition only holds a weak reference, so the rule doesn't keep itself
+/// alive.
+pub struct Recursive<'p, I, T> {
+    slot: Weak<Slot<'p, I, T>>,
+    owner: Option<Rc<Slot<'p, I, T>>>,
+}
+
+impl<I, T> Clone for Recursive<'_, I, T> {
+    fn clone(&self) -> Self {
+        Recursive {
+            slot: self.slot.clone(),
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+impl<I: Input, T> Parser<I, T> for Recursive<'_, I, T> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let slot = self
+            .slot
+            .upgrade()
+            .expect("recursive parser used after its rule was dropped");
+        let rule = slot
+            .get()
+            .expect("recursive parser used while its rule was being defined");
+        rule.run(s)
+    }
+}
+
+/// Builds a rule that can refer to itself: `define` gets a handle standing in
+/// for the finished parser and is called exactly once.
+pub fn recursive<'p, I, T, P, F>(define: F) -> Recursive<'p, I, T>
+where
+    I: Input,
+    P: Parser<I, T> + 'p,
+    F: FnOnce(Recursive<'p, I, T>) -> P,
+{
+    let slot = Rc::new(OnceCell::new());
+    let handle = Recursive {
+        slot: Rc::downgrade(&slot),
+        owner: None,
+    };
+    let rule: Box<dyn Rule<I, T> + 'p> = Box::new(define(handle));
+    let _ = slot.set(rule);
+    Recursive {
+        slot: Rc::downgrade(&slot),
+        owner: Some(slot),
+    }
+}
+
+/// A parser built on first use, see `lazy`.
+pub struct Lazy<'p, I, T> {
+    make: Rc<dyn Fn() -> Box<dyn Rule<I, T> + 'p> + 'p>,
+    slot: Rc<Slot<'p, I, T>>,
+}
+
+impl<I, T> Clone for Lazy<'_, I, T> {
+    fn clone(&self) -> Self {
+        Lazy {
+            make: self.make.clone(),
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<I: Input, T> Parser<I, T> for Lazy<'_, I, T> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        self.slot.get_or_init(|| (self.make)()).run(s)
+    }
+}
+
+/// Defers calling `make` until the parser is first run, then keeps what it
+/// built. Lets rule functions mention each other, as in mutually recursive
+/// grammars, without building forever.
+pub fn lazy<'p, I, T, P, F>(make: F) -> Lazy<'p, I, T>
+where
+    I: Input,
+    P: Parser<I, T> + 'p,
+    F: Fn() -> P + 'p,
+{
+    Lazy {
+        make: Rc::new(move || -> Box<dyn Rule<I, T> + 'p> { Box::new(make()) }),
+        slot: Rc::new(OnceCell::new()),
+    }
+}
+
+/// Matches `s` and returns it as an owned `String`; `tag(s)` is the
+/// borrowed variant, returning the matched slice of the input.
+pub fn token(s: &str) -> impl Parser<&str, String> + Clone {
+    tag(s).map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unicode::GeneralCategory;
+    use crate::Status;
+    use std::cell::Cell;
+
+    fn even<'a>() -> impl Parser<&'a str, usize> + Clone {
+        character('a')
+            .and_then(lazy(odd))
+            .map(|(_, n)| n + 1)
+            .or_else(character('.').map(|_| 0))
+    }
+
+    fn odd<'a>() -> impl Parser<&'a str, usize> + Clone {
+        character('a').and_then(lazy(even)).map(|(_, n)| n + 1)
+    }
+
+    #[test]
+    fn test_recursive() {
+        let depth = recursive(|parens| {
+            character('(')
+                .and_then(parens.optional())
+                .and_then(character(')'))
+                .map(|((_, inner), _)| inner.map_or(1, |d: usize| d + 1))
+        });
+        assert_eq!(Ok((3, "()")), depth.parse("((()))()"));
+        assert!(depth.parse("(()").is_err());
+    }
+
+    #[test]
+    fn test_memo() {
+        let calls = Cell::new(0);
+        let letters = satisfy(|c: &char| {
+            calls.set(calls.get() + 1);
+            c.is_ascii_lowercase()
+        })
+        .many()
+        .memo();
+        let words = letters
+            .clone()
+            .and_then(character('!'))
+            .or_else(letters.and_then(character('?')));
+        assert!(words.parse("abc?").is_ok());
+        assert_eq!(4, calls.get());
+        assert!(words.parse("abc?").is_ok());
+        assert_eq!(8, calls.get());
+    }
+
+    #[test]
+    fn test_left_recursion() {
+        let number = digit()
+            .many1()
+            .map(|ds| ds.iter().fold(0, |n, &d| n * 10 + d as i64));
+        let expr = recursive(|expr| {
+            let factor = number.clone().or_else(
+                character('(')
+                    .and_then(expr.clone())
+                    .and_then(character(')'))
+                    .map(|((_, v), _)| v),
+            );
+            let term = recursive(|term| {
+                term.and_then(one_of("*/"))
+                    .and_then(factor.clone())
+                    .map(|((a, op), b)| if op == '*' { a * b } else { a / b })
+                    .or_else(factor)
+                    .memo()
+            });
+            expr.and_then(one_of("+-"))
+                .and_then(term.clone())
+                .map(|((a, op), b)| if op == '+' { a + b } else { a - b })
+                .or_else(term)
+                .memo()
+        });
+        assert_eq!(Ok((5, "")), expr.parse("8-2-3*2/3+1"));
+        assert_eq!(Ok((8, "")), expr.parse("2*(7-2-1)"));
+        assert_eq!(Ok((1, "-")), expr.parse("1-"));
+    }
+
+    #[test]
+    fn test_indirect_left_recursion() {
+        let append = |(v, c): (String, char)| v + &c.to_string();
+        let a = recursive(|a| {
+            let b = a.and_then(character('z')).map(append);
+            b.and_then(character('x'))
+                .map(append)
+                .or_else(character('y').map(String::from))
+                .memo()
+        });
+        assert_eq!(Ok(("yzxzx".to_string(), "")), a.parse("yzxzx"));
+        // Both rules of the cycle memoized: `b` isn't regrown.
+        let a = recursive(|a| {
+            let b = a.and_then(character('z')).map(append).memo();
+            b.and_then(character('x'))
+                .map(append)
+                .or_else(character('y').map(String::from))
+                .memo()
+        });
+        assert_eq!(Ok(("y".to_string(), "zxzx")), a.parse("yzxzx"));
+    }
+
+    #[test]
+    fn test_chain() {
+        let number = digit().map(i64::from);
+        let minus = character('-').map(|_| |a: i64, b: i64| a - b);
+        let power = character('^').map(|_| |a: i64, b: i64| a.pow(b as u32));
+        assert_eq!(
+            Ok((-4, "")),
+            number.clone().chain_left(minus.clone()).parse("1-2-3")
+        );
+        assert_eq!(
+            Ok((2, "")),
+            number.clone().chain_right(minus).parse("1-2-3")
+        );
+        assert_eq!(Ok((512, "-")), number.chain_right(power).parse("2^3^2-"));
+    }
+
+    #[test]
+    fn test_furthest_failure() {
+        let list = digit()
+            .sep_by(character(','))
+            .between(character('['), character(']'));
+        let err = list.parse("[1,x]").unwrap_err();
+        assert_eq!(
+            (3, &[Expected::Label("digit")][..]),
+            (err.offset(), err.expected())
+        );
+        let err = list.parse("[1x").unwrap_err();
+        assert_eq!(
+            (2, &[Expected::Char(','), Expected::Char(']')][..]),
+            (err.offset(), err.expected())
+        );
+        let digits = character('-').optional().and_then(digit().many());
+        let err = digits.and_then(character(';')).parse("-12x").unwrap_err();
+        assert_eq!(
+            &[Expected::Label("digit"), Expected::Char(';')],
+            err.expected()
+        );
+        let minus = character('-').map(|_| |a: u8, b: u8| a - b);
+        let err = digit()
+            .chain_left(minus)
+            .and_then(end())
+            .parse("3-")
+            .unwrap_err();
+        assert_eq!(
+            (2, &[Expected::Label("digit")][..]),
+            (err.offset(), err.expected())
+        );
+    }
+
+    #[test]
+    fn test_skip_balanced() {
+        let skip = skip_balanced("[]{}", ",]");
+        assert_eq!(Ok(((), ", 3]")), skip.parse("x [1, {\"b\": 2}] y, 3]"));
+        assert_eq!(Ok(((), "], 3")), skip.parse("2 x}], 3"));
+        assert_eq!(Ok(((), "")), skip.parse("[1, 2"));
+        assert_eq!(
+            Ok(Status::Incomplete { needed: 1 }),
+            skip.parse_partial("[1, 2")
+        );
+    }
+
+    #[test]
+    fn test_recognize() {
+        let number = character('-')
+            .optional()
+            .and_then(digit().map(|_| ()).many1());
+        assert_eq!(Ok(("-12", "x")), number.clone().recognize().parse("-12x"));
+        let ((matched, (sign, digits)), _) = number.consumed().parse("12").unwrap();
+        assert_eq!(("12", None, 2), (matched, sign, digits.len()));
+    }
+
+    #[test]
+    fn test_lazy() {
+        assert_eq!(Ok((4, "")), even().parse("aaaa."));
+        assert!(even().parse("aaa.").is_err());
+        assert_eq!(Ok((3, "")), odd().parse("aaa."));
+    }
+
+    #[test]
+    fn test_unicode() {
+        assert_eq!(Ok(("Grüße_2", "=")), identifier().parse("Grüße_2="));
+        assert_eq!(Ok(("_имя", "")), identifier().parse("_имя"));
+        assert_eq!(Ok(("变量", " ")), identifier().parse("变量 "));
+        let err = identifier::<&str>().parse("2x").unwrap_err();
+        assert_eq!(&[Expected::Label("identifier")], err.expected());
+        assert!(identifier::<&str>().parse("\u{301}a").is_err());
+        assert_eq!(Ok(('λ', "")), unicode_letter().parse("λ"));
+        assert!(unicode_letter::<&str>().parse("٣").is_err());
+        assert_eq!(Ok(('\u{3000}', "")), unicode_whitespace().parse("\u{3000}"));
+        let currency = unicode_category(GeneralCategory::CurrencySymbol);
+        assert_eq!(Ok(('€', "5")), currency.parse("€5"));
+    }
+
+    #[test]
+    fn test_take_while() {
+        let ident = take_while1(|c: &char| c.is_alphanumeric() || *c == '_');
+        assert_eq!(Ok(("héllo_1", " x")), ident.parse("héllo_1 x"));
+        assert_eq!(0, ident.parse(" x").unwrap_err().offset());
+        assert_eq!(Ok(("", "x")), take_while(|c: &char| *c == ' ').parse("x"));
+        assert_eq!(
+            Ok(("ab", "\n")),
+            take_till(|c: &char| *c == '\n').parse("ab\n")
+        );
+        let hex = take_while_m_n(2, 4, |c: &char| c.is_ascii_hexdigit());
+        assert_eq!(Ok(("fF0a", "1")), hex.parse("fF0a1"));
+        assert!(hex.parse("f!").is_err());
+        let comment = tag("/*").and_then(take_until("*/"));
+        assert_eq!(Ok((("/*", " a "), "*/")), comment.parse("/* a */"));
+        assert_eq!(6, comment.parse("/* a *").unwrap_err().offset());
+        let status = take_while(|c: &char| c.is_ascii_digit()).parse_partial("12");
+        assert_eq!(Ok(Status::Incomplete { needed: 1 }), status);
+    }
+
+    #[test]
+    fn test_recover_backtracked() {
+        let recovered = digit().recover_with(any().map(|_| 0));
+        let failed = recovered.clone().and_then(character('!'));
+        let (v, errors) = failed.parse_recovery("x;");
+        let offsets: Vec<usize> = errors.iter().map(ParseError::offset).collect();
+        assert_eq!((None, vec![0, 1]), (v, offsets));
+        let either = failed
+            .map(|_| ())
+            .or_else(tag("x1").and_then(character('!')).map(|_| ()));
+        let (v, errors) = either.parse_recovery("x1;");
+        let offsets: Vec<usize> = errors.iter().map(ParseError::offset).collect();
+        assert_eq!((None, vec![2]), (v, offsets));
+    }
 }
@@ -0,0 +1,194 @@
+use crate::Expected;
+use parsecore::line_column;
+use std::fmt::Debug;
+
+/// Something parsers can consume: `&str` yields `char`s at byte offsets, any
+/// other slice (`&[u8]`, `&[char]`, pre-lexed tokens) yields its elements at
+/// element offsets.
+pub trait Input: Copy {
+    type Item: Clone + PartialEq + Debug;
+
+    /// Length in offset units.
+    fn input_len(&self) -> usize;
+
+    /// The item starting at `offset` and how many offset units it takes.
+    fn item_at(&self, offset: usize) -> Option<(Self::Item, usize)>;
+
+    fn slice(&self, from: usize, to: usize) -> Self;
+
+    fn starts_with_at(&self, offset: usize, prefix: Self) -> bool;
+
+    /// Counts up to `max` items from `offset` on for which `pred` holds,
+    /// returning the count and the offset just past the last of them.
+    fn scan_while<F>(&self, offset: usize, max: usize, mut pred: F) -> (usize, usize)
+    where
+        F: FnMut(&Self::Item) -> bool,
+    {
+        let (mut count, mut at) = (0, offset);
+        while count < max {
+            match self.item_at(at) {
+                Some((item, len)) if pred(&item) => at += len,
+                _ => break,
+            }
+            count += 1;
+        }
+        (count, at)
+    }
+
+    /// Offset of the first occurrence of `needle` at or after `offset`.
+    fn find_at(&self, offset: usize, needle: Self) -> Option<usize> {
+        (offset..=self.input_len()).find(|&at| self.starts_with_at(at, needle))
+    }
+
+    fn line_column(&self, offset: usize) -> (usize, usize);
+
+    /// The input as text, for inputs that are text already.
+    fn as_str(&self) -> Option<&str> {
+        None
+    }
+
+    /// How an item shows up in an error's expectations.
+    fn expected_item(item: Self::Item) -> Expected;
+
+    /// How a whole tag shows up in an error's expectations.
+    fn expected_tag(&self) -> Expected;
+}
+
+impl Input for &str {
+    type Item = char;
+
+    fn input_len(&self) -> usize {
+        self.len()
+    }
+
+    fn item_at(&self, offset: usize) -> Option<(char, usize)> {
+        self[offset..].chars().next().map(|c| (c, c.len_utf8()))
+    }
+
+    fn slice(&self, from: usize, to: usize) -> Self {
+        &self[from..to]
+    }
+
+    fn starts_with_at(&self, offset: usize, prefix: Self) -> bool {
+        self[offset..].starts_with(prefix)
+    }
+
+    /// ASCII bytes are tested as they are; only the rest get decoded.
+    fn scan_while<F>(&self, offset: usize, max: usize, mut pred: F) -> (usize, usize)
+    where
+        F: FnMut(&char) -> bool,
+    {
+        let bytes = self.as_bytes();
+        let (mut count, mut at) = (0, offset);
+        while count < max && at < bytes.len() {
+            let (c, len) = match bytes[at] {
+                b if b.is_ascii() => (b as char, 1),
+                _ => self.item_at(at).unwrap(),
+            };
+            if !pred(&c) {
+                break;
+            }
+            at += len;
+            count += 1;
+        }
+        (count, at)
+    }
+
+    fn find_at(&self, offset: usize, needle: Self) -> Option<usize> {
+        self[offset..].find(needle).map(|at| offset + at)
+    }
+
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        line_column(self, offset)
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        Some(self)
+    }
+
+    fn expected_item(item: char) -> Expected {
+        Expected::Char(item)
+    }
+
+    fn expected_tag(&self) -> Expected {
+        Expected::Token(self.to_string())
+    }
+}
+
+impl<T> Input for &[T]
+where
+    T: Clone + PartialEq + Debug,
+{
+    type Item = T;
+
+    fn input_len(&self) -> usize {
+        self.len()
+    }
+
+    fn item_at(&self, offset: usize) -> Option<(T, usize)> {
+        self.get(offset).map(|t| (t.clone(), 1))
+    }
+
+    fn slice(&self, from: usize, to: usize) -> Self {
+        &self[from..to]
+    }
+
+    fn starts_with_at(&self, offset: usize, prefix: Self) -> bool {
+        self[offset..].starts_with(prefix)
+    }
+
+    /// Slices have no lines; the column is the 1-based element position.
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        (1, offset + 1)
+    }
+
+    fn expected_item(item: T) -> Expected {
+        Expected::Item(format!("{:?}", item))
+    }
+
+    fn expected_tag(&self) -> Expected {
+        Expected::Item(format!("{:?}", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helper::*;
+    use crate::{Expected, Parser};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Tok {
+        Num(i64),
+        Plus,
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        let input: &[u8] = b"GIF89a";
+        let (v, r) = tag(&b"GIF8"[..]).and_then(any()).parse(input).unwrap();
+        assert_eq!((&b"GIF8"[..], b'9'), v);
+        assert_eq!(b"a", r);
+    }
+
+    #[test]
+    fn test_parse_chars() {
+        let input: Vec<char> = "ab1".chars().collect();
+        let (v, r) = letter().many().parse(&input[..]).unwrap();
+        assert_eq!(vec!['a', 'b'], v);
+        assert_eq!(&['1'], r);
+    }
+
+    #[test]
+    fn test_parse_tokens() {
+        let input = [Tok::Num(1), Tok::Plus, Tok::Num(2)];
+        let num = satisfy(|t: &Tok| matches!(t, Tok::Num(_))).map(|t| match t {
+            Tok::Num(n) => n,
+            _ => unreachable!(),
+        });
+        let (v, _) = num.sep_by(item(Tok::Plus)).parse(&input[..]).unwrap();
+        assert_eq!(vec![1, 2], v);
+        let err = item(Tok::Plus).parse(&input[..]).unwrap_err();
+        assert_eq!(0, err.offset());
+        assert_eq!(&[Expected::Item("Plus".to_owned())], err.expected());
+    }
+}
@@ -1,15 +1,102 @@
 use crate::combinator::*;
+use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
+pub mod async_reader;
+pub mod binary;
+mod charset;
 mod combinator;
+mod error;
 pub mod helper;
+mod input;
+pub mod number;
+pub mod pratt;
+pub mod reader;
+mod state;
 
-pub trait Parser<T>: Sized {
-    fn parse<'a>(&self, s: &'a str) -> Option<(T, &'a str)>;
+pub use crate::error::{Expected, ParseError};
+pub use crate::input::Input;
+pub use crate::state::{Spanned, State};
+pub use parsecore::{line_column, unicode, Category, CharSet};
+
+mod sealed {
+    /// Stands in for "called from inside this crate" in `Parser` methods that
+    /// other crates mustn't override.
+    pub struct Sealed;
+}
+
+use crate::sealed::Sealed;
+
+pub type ParseResult<'s, I, T> = Result<(T, State<'s, I>), ParseError>;
+
+/// Outcome of `parse_partial`: either a value and the unparsed rest, or how
+/// many more items are needed before the parser can decide.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status<T, I> {
+    Done(T, I),
+    Incomplete { needed: usize },
+}
+
+pub trait Parser<I: Input, T>: Sized {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T>;
+
+    fn parse(&self, s: I) -> Result<(T, I), ParseError> {
+        self.parse_at(State::new(s)).map(|(v, r)| (v, r.rest()))
+    }
+
+    /// Parses `s` as the beginning of an input that's still arriving: running
+    /// off its end gives `Status::Incomplete` instead of an error, so the
+    /// caller can append more data and parse again. `parse` is the complete
+    /// counterpart, where the end of `s` is the end of the input.
+    fn parse_partial(&self, s: I) -> Result<Status<T, I>, ParseError> {
+        match self.parse_at(State::partial(s)) {
+            Ok((v, r)) => Ok(Status::Done(v, r.rest())),
+            Err(e) => match e.needed() {
+                Some(needed) => Ok(Status::Incomplete { needed }),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Parses `s` with `recover_with` enabled, returning whatever value could
+    /// be built together with every error that was recovered from. If the
+    /// parse still fails, the final error comes last.
+    fn parse_recovery(&self, s: I) -> (Option<T>, Vec<ParseError>) {
+        let diagnostics = RefCell::new(Vec::new());
+        match self.parse_at(State::recovering(s, &diagnostics)) {
+            Ok((v, r)) => (Some(v), r.recovered()),
+            Err(e) => {
+                let mut errors = diagnostics.take();
+                errors.truncate(e.recorded());
+                errors.push(e.after_recording(0));
+                (None, errors)
+            }
+        }
+    }
+
+    /// The characters this parser accepts, when it matches a single one of
+    /// them and returns it, as `character` and `character_range` do. Chains of
+    /// `or_else` over such parsers are merged into one set lookup.
+    ///
+    /// This and `parse_set` have to agree with `parse_at`, so only parsers of
+    /// this crate can override them: nothing else can name `Sealed`.
+    #[doc(hidden)]
+    fn char_set(&self, _: Sealed) -> Option<CharSet> {
+        None
+    }
+
+    /// Parses one character of `set`, which `char_set` has merged from this
+    /// parser's and others'. Only called on parsers with a `char_set`.
+    #[doc(hidden)]
+    fn parse_set<'s>(&self, set: &CharSet, s: State<'s, I>, _: Sealed) -> ParseResult<'s, I, T> {
+        let _ = set;
+        self.parse_at(s)
+    }
 
     fn and_then<P, U>(self, next: P) -> AndThen<Self, P>
     where
-        P: Parser<U>,
+        P: Parser<I, U>,
     {
         AndThen {
             parser1: self,
@@ -19,11 +106,16 @@ pub trait Parser<T>: Sized {
 
     fn or_else<P>(self, alternative: P) -> OrElse<Self, P>
     where
-        P: Parser<T>,
+        P: Parser<I, T>,
     {
+        let merged = match (self.char_set(Sealed), alternative.char_set(Sealed)) {
+            (Some(set1), Some(set2)) => set1.union(&set2),
+            _ => None,
+        };
         OrElse {
             parser1: self,
             parser2: alternative,
+            merged,
         }
     }
 
@@ -69,7 +161,7 @@ pub trait Parser<T>: Sized {
 
     fn except<P>(self, p: P) -> Except<Self, P>
     where
-        P: Parser<T>,
+        P: Parser<I, T>,
     {
         Except {
             parser1: self,
@@ -79,7 +171,7 @@ pub trait Parser<T>: Sized {
 
     fn sep_by<P, U>(self, sep: P) -> SepBy<Self, P, U>
     where
-        P: Parser<U>,
+        P: Parser<I, U>,
     {
         SepBy {
             parser: self,
@@ -88,10 +180,38 @@ pub trait Parser<T>: Sized {
         }
     }
 
+    /// One or more of this parser separated by `op`, folded from the left with
+    /// the functions `op` returns: `1 - 2 - 3` is `(1 - 2) - 3`.
+    fn chain_left<P, F>(self, op: P) -> ChainLeft<Self, P, F>
+    where
+        P: Parser<I, F>,
+        F: Fn(T, T) -> T,
+    {
+        ChainLeft {
+            parser: self,
+            op,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `chain_left`, but folded from the right: `2 ^ 3 ^ 2` is
+    /// `2 ^ (3 ^ 2)`.
+    fn chain_right<P, F>(self, op: P) -> ChainRight<Self, P, F>
+    where
+        P: Parser<I, F>,
+        F: Fn(T, T) -> T,
+    {
+        ChainRight {
+            parser: self,
+            op,
+            _marker: PhantomData,
+        }
+    }
+
     fn between<P2, P3, U, V>(self, start: P2, end: P3) -> Between<Self, P2, P3, U, V>
     where
-        P2: Parser<U>,
-        P3: Parser<V>,
+        P2: Parser<I, U>,
+        P3: Parser<I, V>,
     {
         Between {
             parser: self,
@@ -100,4 +220,83 @@ pub trait Parser<T>: Sized {
             _marker: PhantomData,
         }
     }
+
+    /// Commits to this parser: if it fails, enclosing `or_else`, `optional`,
+    /// `many` and `sep_by` report the failure instead of trying something else.
+    fn cut(self) -> Cut<Self> {
+        Cut { parser: self }
+    }
+
+    /// Names this parser in error messages: a failure that didn't get past the
+    /// start of the input reports `expected <name>` instead of the raw
+    /// expectations of its parts, and one right where it ended reports what
+    /// was expected `after <name>`.
+    fn label(self, name: &'static str) -> Label<Self> {
+        Label { parser: self, name }
+    }
+
+    /// Adds `name` to the breadcrumb trail of failures that happen after this
+    /// parser has consumed some input.
+    fn context(self, name: &'static str) -> Context<Self> {
+        Context { parser: self, name }
+    }
+
+    /// When parsing with `parse_recovery` and this parser fails, records the
+    /// error and runs `strategy` from where the error happened instead; its
+    /// value stands in for the one that couldn't be parsed. Outside of
+    /// recovery mode the error is returned as is.
+    fn recover_with<P>(self, strategy: P) -> RecoverWith<Self, P>
+    where
+        P: Parser<I, T>,
+    {
+        RecoverWith {
+            parser: self,
+            strategy,
+        }
+    }
+
+    /// The slice of the input this parser matched, instead of its value.
+    /// Paired with a parser whose value is `()`, as in
+    /// `digit().map(|_| ()).many1().recognize()`, nothing gets allocated.
+    fn recognize(self) -> Recognize<Self, T> {
+        Recognize {
+            parser: self,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The slice of the input this parser matched, along with its value.
+    fn consumed(self) -> Consumed<Self> {
+        Consumed { parser: self }
+    }
+
+    fn spanned(self) -> Spanning<Self> {
+        Spanning { parser: self }
+    }
+
+    /// Remembers this parser's result at every offset it's tried at, for the
+    /// rest of the parse run: trying it again at the same place, say from
+    /// another alternative after backtracking, costs a lookup instead of a
+    /// reparse. Clones share the table.
+    ///
+    /// Memoizing a rule also makes it safe for it to call itself first thing,
+    /// as in `expr := expr '-' term | term`: the recursive call fails at first,
+    /// and the rule then reruns with each result standing in for the recursive
+    /// call for as long as that gets further into the input, so the parse is
+    /// left associative. Put the `memo` inside `recursive` so the recursive
+    /// handle goes through it.
+    ///
+    /// Only the memoized rule itself is regrown. When left recursion goes
+    /// through other rules first, as in `a := b 'x' | 'y'` with
+    /// `b := a 'z'`, memoize just one rule of the cycle: a memoized `b` would
+    /// keep the result of its first try, and `a` would stop after the `'y'`.
+    fn memo(self) -> Memo<Self, T>
+    where
+        T: Clone,
+    {
+        Memo {
+            parser: self,
+            table: Rc::new(RefCell::new(MemoTable::new())),
+        }
+    }
 }
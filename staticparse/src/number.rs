@@ -0,0 +1,245 @@
+use crate::{Expected, Input, ParseError, ParseResult, Parser, State};
+use parsecore::number::{FloatFormat, IntegerFormat, ScanError, Source};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+pub use parsecore::number::{Float, Integer};
+
+/// The characters of an `Input`, for the number scanners `parsecore` shares.
+struct Chars<I>(I);
+
+impl<I: Input<Item = char>> Source for Chars<I> {
+    fn char_at(&self, at: usize) -> Option<(char, usize)> {
+        self.0.item_at(at)
+    }
+
+    fn digits_end(&self, at: usize) -> usize {
+        self.0.scan_while(at, usize::MAX, |c| c.is_ascii_digit()).1
+    }
+
+    fn parse_text<T: FromStr>(&self, start: usize, end: usize) -> Option<T> {
+        let text = self.0.slice(start, end);
+        match text.as_str() {
+            Some(text) => text.parse().ok(),
+            None => collect(text).parse().ok(),
+        }
+    }
+}
+
+/// The characters of an input that isn't text already.
+fn collect<I: Input<Item = char>>(input: I) -> String {
+    let mut text = String::new();
+    let mut at = 0;
+    while let Some((c, len)) = input.item_at(at) {
+        text.push(c);
+        at += len;
+    }
+    text
+}
+
+impl From<ScanError> for ParseError {
+    fn from(e: ScanError) -> Self {
+        match e {
+            ScanError::Incomplete(at) => ParseError::incomplete(at, 1),
+            ScanError::Expected(at, label) => ParseError::new(at, vec![Expected::Label(label)]),
+        }
+    }
+}
+
+/// Parses the digits of an integer in some radix, failing rather than
+/// wrapping when the number doesn't fit in `T`.
+#[derive(Clone)]
+pub struct IntegerParser<T> {
+    format: IntegerFormat,
+    _marker: PhantomData<T>,
+}
+
+impl<T> IntegerParser<T> {
+    /// Digits in `radix`, from 2 to 36, without a sign.
+    pub fn new(radix: u32) -> Self {
+        IntegerParser {
+            format: IntegerFormat::new(radix),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Accepts a leading `+` or `-`.
+    pub fn signed(mut self) -> Self {
+        self.format = self.format.signed();
+        self
+    }
+
+    /// Accepts single underscores between digits, as in `1_000_000`.
+    pub fn underscores(mut self) -> Self {
+        self.format = self.format.underscores();
+        self
+    }
+}
+
+impl<I: Input<Item = char>, T: Integer> Parser<I, T> for IntegerParser<T> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        let (value, end) = self
+            .format
+            .scan(&Chars(s.source()), offset, s.is_partial())?;
+        Ok((value, s.advance(end - offset)))
+    }
+}
+
+/// A decimal integer with an optional sign.
+pub fn integer<T: Integer>() -> IntegerParser<T> {
+    IntegerParser::new(10).signed()
+}
+
+/// A decimal integer without a sign.
+pub fn unsigned<T: Integer>() -> IntegerParser<T> {
+    IntegerParser::new(10)
+}
+
+/// Hexadecimal digits, either case, without a `0x` prefix.
+pub fn hex_integer<T: Integer>() -> IntegerParser<T> {
+    IntegerParser::new(16)
+}
+
+pub fn octal<T: Integer>() -> IntegerParser<T> {
+    IntegerParser::new(8)
+}
+
+pub fn binary<T: Integer>() -> IntegerParser<T> {
+    IntegerParser::new(2)
+}
+
+/// Parses a floating-point number, converting what it matched with correct
+/// rounding. By default it takes the decimal syntax `-12.5e-3`, `.5` and `1.`
+/// included; the builder methods enable more.
+#[derive(Clone)]
+pub struct FloatParser<T> {
+    format: FloatFormat,
+    _marker: PhantomData<T>,
+}
+
+impl<T> FloatParser<T> {
+    /// Also accepts `inf`, `infinity` and `nan`, in any case.
+    pub fn special(mut self) -> Self {
+        self.format = self.format.special();
+        self
+    }
+
+    /// Also accepts a leading `+`.
+    pub fn plus(mut self) -> Self {
+        self.format = self.format.plus();
+        self
+    }
+
+    /// Also accepts hexadecimal floats such as `0x1.8p3`, where the binary
+    /// exponent is required.
+    pub fn hex(mut self) -> Self {
+        self.format = self.format.hex();
+        self
+    }
+}
+
+impl<I: Input<Item = char>, T: Float> Parser<I, T> for FloatParser<T> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let offset = s.offset();
+        let (value, end) = self
+            .format
+            .scan(&Chars(s.source()), offset, s.is_partial())?;
+        Ok((value, s.advance(end - offset)))
+    }
+}
+
+/// A decimal floating-point number, see `FloatParser` for the options.
+pub fn float<T: Float>() -> FloatParser<T> {
+    FloatParser {
+        format: FloatFormat::new(),
+        _marker: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helper::tag;
+    use crate::number::*;
+    use crate::{Expected, Parser, Status};
+
+    #[test]
+    fn test_parse_integers() {
+        assert_eq!(Ok((-42_i64, "x")), integer().parse("-42x"));
+        assert_eq!(Ok((i64::MIN, "")), integer().parse("-9223372036854775808"));
+        let max = u128::MAX.to_string();
+        assert_eq!(Ok((u128::MAX, "")), unsigned().parse(max.as_str()));
+        assert_eq!(Ok((0xff_u8, "g")), hex_integer().parse("fFg"));
+        assert_eq!(Ok((0o17_u16, "8")), octal().parse("178"));
+        let bits = tag("0b").and_then(binary::<u8>().underscores());
+        assert_eq!(Ok((("0b", 0b1010_0101), "_")), bits.parse("0b1010_0101_"));
+    }
+
+    #[test]
+    fn test_parse_overflow() {
+        let err = integer::<i32>().parse("x2147483648").unwrap_err();
+        assert_eq!(&[Expected::Label("digit")], err.expected());
+        let err = integer::<i32>().parse("2147483648").unwrap_err();
+        assert_eq!(0, err.offset());
+        assert_eq!(&[Expected::Label("integer in i32 range")], err.expected());
+        assert!(unsigned::<u8>().parse("256").is_err());
+        assert!(integer::<u8>().parse("-1").is_err());
+        assert_eq!(Ok((0_u8, "")), integer().parse("-0"));
+        let status = unsigned::<u32>().parse_partial("12");
+        assert_eq!(Ok(Status::Incomplete { needed: 1 }), status);
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(Ok((1.05_f64, "")), float().parse("1.05"));
+        assert_eq!(Ok((-2.5e-3_f64, ",")), float().parse("-2.5e-3,"));
+        assert_eq!(Ok((0.5_f64, "")), float().parse(".5"));
+        assert_eq!(Ok((1_f32, "e")), float().parse("1.e"));
+        assert_eq!(Ok((1e300_f64, "x")), float().parse("1E+300x"));
+        let (zero, _) = float::<f64>().parse("-0.0").unwrap();
+        assert!(zero == 0.0 && zero.is_sign_negative());
+        let err = float::<f64>().parse("-.e1").unwrap_err();
+        assert_eq!(
+            (1, &[Expected::Label("number")][..]),
+            (err.offset(), err.expected())
+        );
+        assert!(float::<f64>().parse("+1").is_err());
+        assert_eq!(Ok((1_f64, "")), float().plus().parse("+1"));
+        assert!(float::<f64>().parse("inf").is_err());
+        let special = float::<f64>().special();
+        assert_eq!(Ok((f64::NEG_INFINITY, "")), special.parse("-Infinity"));
+        assert_eq!(Ok((f64::INFINITY, "x")), special.parse("infx"));
+        assert!(special.parse("NaN").unwrap().0.is_nan());
+        let chars: Vec<char> = "2.5]".chars().collect();
+        assert_eq!(Ok((2.5_f64, &[']'][..])), float().parse(&chars[..]));
+        let status = float::<f64>().parse_partial("1.5e");
+        assert_eq!(Ok(Status::Incomplete { needed: 1 }), status);
+    }
+
+    #[test]
+    fn test_parse_hex_float() {
+        let hex = float::<f64>().hex();
+        assert_eq!(Ok((12.0, "")), hex.parse("0x1.8p3"));
+        assert_eq!(Ok((-0.25, "")), hex.parse("-0X1P-2"));
+        assert_eq!(Ok((0.0, "x1")), hex.parse("0x1"));
+        assert_eq!(Ok((f64::from_bits(1), "")), hex.parse("0x1p-1074"));
+        assert_eq!(Ok((f64::from_bits(2), "")), hex.parse("0x1.8p-1074"));
+        assert_eq!(Ok((0.0, "")), hex.parse("0x1p-1075"));
+        assert_eq!(
+            Ok((f64::MIN_POSITIVE, "")),
+            hex.parse("0x0.fffffffffffff8p-1022")
+        );
+        assert_eq!(Ok((f64::MAX, "")), hex.parse("0x1.fffffffffffff7ffp1023"));
+        assert_eq!(
+            Ok((f64::INFINITY, "")),
+            hex.parse("0x1.fffffffffffff8p1023")
+        );
+        let hex = float::<f32>().hex();
+        assert_eq!(Ok((1.0 + f32::EPSILON, "")), hex.parse("0x1.000002p0"));
+        assert_eq!(Ok((1.0, "")), hex.parse("0x1.000001p0"));
+        assert_eq!(
+            Ok((1.0 + 2.0 * f32::EPSILON, "")),
+            hex.parse("0x1.000003p0")
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use crate::helper::Rule;
+use crate::{Input, ParseError, ParseResult, Parser, State};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// How a chain of infix operators of the same power groups: `a - b - c` is
+/// `(a - b) - c` for `Left`, `a ^ b ^ c` is `a ^ (b ^ c)` for `Right`, and
+/// `a == b == c` is an error for `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+/// An operator parser with its value thrown away.
+struct Op<P, O> {
+    parser: P,
+    _marker: PhantomData<O>,
+}
+
+impl<I: Input, P: Parser<I, O>, O> Parser<I, ()> for Op<P, O> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, ()> {
+        self.parser.parse_at(s).map(|(_, r)| ((), r))
+    }
+}
+
+fn op<'p, I, P, O>(parser: P) -> Rc<dyn Rule<I, ()> + 'p>
+where
+    I: Input + 'p,
+    P: Parser<I, O> + 'p,
+    O: 'p,
+{
+    Rc::new(Op {
+        parser,
+        _marker: PhantomData,
+    })
+}
+
+fn merge(error: Option<ParseError>, e: ParseError) -> ParseError {
+    match error {
+        Some(error) => error.merge(e),
+        None => e,
+    }
+}
+
+struct Unary<'p, I, T> {
+    op: Rc<dyn Rule<I, ()> + 'p>,
+    power: u32,
+    fold: Rc<dyn Fn(T) -> T + 'p>,
+}
+
+impl<I, T> Clone for Unary<'_, I, T> {
+    fn clone(&self) -> Self {
+        Unary {
+            op: self.op.clone(),
+            power: self.power,
+            fold: self.fold.clone(),
+        }
+    }
+}
+
+struct Binary<'p, I, T> {
+    op: Rc<dyn Rule<I, ()> + 'p>,
+    assoc: Assoc,
+    power: u32,
+    fold: Rc<dyn Fn(T, T) -> T + 'p>,
+}
+
+impl<I, T> Clone for Binary<'_, I, T> {
+    fn clone(&self) -> Self {
+        Binary {
+            op: self.op.clone(),
+            assoc: self.assoc,
+            power: self.power,
+            fold: self.fold.clone(),
+        }
+    }
+}
+
+impl<I, T> Binary<'_, I, T> {
+    /// How tightly the operator holds on to its left and right operands.
+    fn binding(&self) -> (u32, u32) {
+        match self.assoc {
+            Assoc::Left | Assoc::None => (2 * self.power, 2 * self.power + 1),
+            Assoc::Right => (2 * self.power + 1, 2 * self.power),
+        }
+    }
+}
+
+/// An operator-precedence expression parser.
+///
+/// Operands come from `atom`; operators are registered with a power, higher
+/// binding tighter, and a function folding their operands into one value.
+/// Operator parsers' own values are ignored, so register operators that fold
+/// differently separately. Wrap the builder in `recursive` when atoms contain
+/// whole expressions, as parenthesized ones do.
+pub struct Pratt<'p, I, T> {
+    atom: Rc<dyn Rule<I, T> + 'p>,
+    prefix: Vec<Unary<'p, I, T>>,
+    infix: Vec<Binary<'p, I, T>>,
+    postfix: Vec<Unary<'p, I, T>>,
+}
+
+impl<I, T> Clone for Pratt<'_, I, T> {
+    fn clone(&self) -> Self {
+        Pratt {
+            atom: self.atom.clone(),
+            prefix: self.prefix.clone(),
+            infix: self.infix.clone(),
+            postfix: self.postfix.clone(),
+        }
+    }
+}
+
+impl<'p, I: Input + 'p, T> Pratt<'p, I, T> {
+    pub fn new<P: Parser<I, T> + 'p>(atom: P) -> Self {
+        Pratt {
+            atom: Rc::new(atom),
+            prefix: Vec::new(),
+            infix: Vec::new(),
+            postfix: Vec::new(),
+        }
+    }
+
+    pub fn prefix<P, O, F>(mut self, power: u32, op: P, fold: F) -> Self
+    where
+        P: Parser<I, O> + 'p,
+        O: 'p,
+        F: Fn(T) -> T + 'p,
+    {
+        self.prefix.push(Unary {
+            op: self::op(op),
+            power,
+            fold: Rc::new(fold),
+        });
+        self
+    }
+
+    pub fn infix<P, O, F>(mut self, assoc: Assoc, power: u32, op: P, fold: F) -> Self
+    where
+        P: Parser<I, O> + 'p,
+        O: 'p,
+        F: Fn(T, T) -> T + 'p,
+    {
+        self.infix.push(Binary {
+            op: self::op(op),
+            assoc,
+            power,
+            fold: Rc::new(fold),
+        });
+        self
+    }
+
+    pub fn postfix<P, O, F>(mut self, power: u32, op: P, fold: F) -> Self
+    where
+        P: Parser<I, O> + 'p,
+        O: 'p,
+        F: Fn(T) -> T + 'p,
+    {
+        self.postfix.push(Unary {
+            op: self::op(op),
+            power,
+            fold: Rc::new(fold),
+        });
+        self
+    }
+
+    /// An atom, or a prefix operator applied to an operand.
+    fn operand<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        let mut error: Option<ParseError> = None;
+        for prefix in &self.prefix {
+            match prefix.op.run(s.clone()) {
+                Ok(((), r)) => {
+                    let (v, r) = self.expression(r, 2 * prefix.power)?;
+                    return Ok(((prefix.fold)(v), r));
+                }
+                Err(e) if e.is_fatal() => return Err(e),
+                Err(e) => error = Some(merge(error, s.abandon(e))),
+            }
+        }
+        self.atom.run(s).map_err(|e| merge(error, e))
+    }
+
+    /// An expression whose operators all bind at least as tightly as `min`.
+    fn expression<'s>(&self, s: State<'s, I>, min: u32) -> ParseResult<'s, I, T> {
+        let (mut lhs, mut s) = self.operand(s)?;
+        let mut chained: Option<u32> = None;
+        'operators: loop {
+            for postfix in self.postfix.iter().filter(|op| 2 * op.power >= min) {
+                match postfix.op.run(s.clone()) {
+                    Ok(((), r)) => {
+                        lhs = (postfix.fold)(lhs);
+                        s = r;
+                        continue 'operators;
+                    }
+                    Err(e) => s = s.backtrack(e)?,
+                }
+            }
+            for infix in &self.infix {
+                let (left, right) = infix.binding();
+                if left < min {
+                    continue;
+                }
+                match infix.op.run(s.clone()) {
+                    Ok(((), r)) => {
+                        if infix.assoc == Assoc::None && chained == Some(infix.power) {
+                            return Err(ParseError::unexpected(s.offset()));
+                        }
+                        let (rhs, r) = self.expression(r, right)?;
+                        lhs = (infix.fold)(lhs, rhs);
+                        s = r;
+                        chained = (infix.assoc == Assoc::None).then_some(infix.power);
+                        continue 'operators;
+                    }
+                    Err(e) => s = s.backtrack(e)?,
+                }
+            }
+            return Ok((lhs, s));
+        }
+    }
+}
+
+impl<'p, I: Input + 'p, T> Parser<I, T> for Pratt<'p, I, T> {
+    fn parse_at<'s>(&self, s: State<'s, I>) -> ParseResult<'s, I, T> {
+        self.expression(s, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::*;
+
+    fn calculator<'a>() -> impl Parser<&'a str, i64> + Clone {
+        recursive(|expr| {
+            let number = digit()
+                .many1()
+                .map(|ds| ds.iter().fold(0, |n, &d| n * 10 + d as i64));
+            let parens = character('(')
+                .and_then(expr)
+                .and_then(character(')'))
+                .map(|((_, v), _)| v);
+            Pratt::new(number.or_else(parens))
+                .infix(Assoc::None, 1, character('='), |a, b| (a == b) as i64)
+                .infix(Assoc::Left, 2, character('+'), |a, b| a + b)
+                .infix(Assoc::Left, 2, character('-'), |a, b| a - b)
+                .infix(Assoc::Left, 3, character('*'), |a, b| a * b)
+                .prefix(4, character('-'), |a| -a)
+                .infix(Assoc::Right, 5, character('^'), |a, b| a.pow(b as u32))
+                .postfix(6, character('!'), |a| (1..=a).product())
+        })
+    }
+
+    #[test]
+    fn test_pratt() {
+        let calc = calculator();
+        assert_eq!(Ok((3, "")), calc.parse("10-4-3"));
+        assert_eq!(Ok((512, "")), calc.parse("2^3^2"));
+        assert_eq!(Ok((14, "")), calc.parse("2+3*4"));
+        assert_eq!(Ok((-4, "")), calc.parse("-2^2"));
+        assert_eq!(Ok((20, "")), calc.parse("(1+2)!*3+2"));
+        assert_eq!(Ok((1, "")), calc.parse("2*3=3!"));
+        assert!(calc.parse("1=1=1").is_err());
+        assert!(calc.parse("1+").is_err());
+    }
+}
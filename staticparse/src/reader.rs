@@ -0,0 +1,502 @@
+use crate::{Input, ParseError, ParseResult, State};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::str;
+
+pub(crate) const CHUNK: usize = 8 * 1024;
+
+/// How a `Reader` presents its buffered bytes to parsers.
+pub trait Encoding {
+    type Input<'b>: Input;
+
+    /// The longest parsable prefix of `bytes`, and the offset of the first
+    /// byte that can never be part of a valid input if there is one. `eof`
+    /// says whether more bytes may still follow.
+    fn decode(bytes: &[u8], eof: bool) -> (Self::Input<'_>, Option<usize>);
+}
+
+/// Hands parsers UTF-8 text, holding back a character split across reads.
+pub struct Text;
+
+impl Encoding for Text {
+    type Input<'b> = &'b str;
+
+    fn decode(bytes: &[u8], eof: bool) -> (&str, Option<usize>) {
+        match str::from_utf8(bytes) {
+            Ok(s) => (s, None),
+            Err(e) => {
+                let valid = str::from_utf8(&bytes[..e.valid_up_to()]).unwrap();
+                let invalid = e.error_len().is_some() || eof;
+                (valid, invalid.then_some(e.valid_up_to()))
+            }
+        }
+    }
+}
+
+/// Hands parsers the raw bytes.
+pub struct Bytes;
+
+impl Encoding for Bytes {
+    type Input<'b> = &'b [u8];
+
+    fn decode(bytes: &[u8], _eof: bool) -> (&[u8], Option<usize>) {
+        (bytes, None)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The source isn't valid UTF-8 at this byte offset.
+    Encoding(usize),
+    /// A record matched at this offset without consuming anything, so every
+    /// later record would match the same nothing.
+    Empty(usize),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Parse(e) => write!(f, "{}", e),
+            ReadError::Encoding(offset) => write!(f, "invalid UTF-8 at offset {}", offset),
+            ReadError::Empty(offset) => write!(f, "empty record at offset {}", offset),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(e) => Some(e),
+            ReadError::Parse(e) => Some(e),
+            ReadError::Encoding(_) | ReadError::Empty(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+/// Bytes read from a source that parsers haven't consumed yet, shared by the
+/// blocking and async drivers.
+///
+/// `wanted` is how many more bytes to read before the parser is worth running
+/// again. Parsers can't pick up where they left off, so every retry starts
+/// over from `start`; once a value outgrows a chunk, the buffer waits for at
+/// least as much again as it already holds, which keeps the retries for a
+/// value of n bytes down to about log(n).
+pub(crate) struct Buffer {
+    bytes: Vec<u8>,
+    start: usize,
+    end: usize,
+    discarded: usize,
+    eof: bool,
+    wanted: usize,
+}
+
+pub(crate) enum Step<T> {
+    Done(T),
+    Pending,
+}
+
+impl Buffer {
+    pub(crate) fn new() -> Self {
+        Buffer {
+            bytes: Vec::new(),
+            start: 0,
+            end: 0,
+            discarded: 0,
+            eof: false,
+            wanted: 0,
+        }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.discarded + self.start
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub(crate) fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// How many more bytes to read before running the parser again.
+    pub(crate) fn wanted(&self) -> usize {
+        if self.eof {
+            0
+        } else {
+            self.wanted
+        }
+    }
+
+    /// Passes on the value of a record that started at `start`, unless it
+    /// consumed nothing.
+    pub(crate) fn advanced<T>(&self, start: usize, v: T) -> Result<T, ReadError> {
+        if self.offset() == start {
+            Err(ReadError::Empty(start))
+        } else {
+            Ok(v)
+        }
+    }
+
+    /// Asks for at least `n` more bytes before the next run.
+    pub(crate) fn want(&mut self, n: usize) {
+        self.wanted = self.wanted.max(n);
+    }
+
+    /// Runs `parse` over the buffered bytes, partially unless the source has
+    /// ended, consuming what it parsed on success. When it needs more input,
+    /// works out how much to read before the next run. Bytes that don't
+    /// decode are held back, and only become an error once the parser needs
+    /// to read past them.
+    pub(crate) fn run<E, T, F>(&mut self, parse: &mut F) -> Result<Step<T>, ReadError>
+    where
+        E: Encoding,
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        let offset = self.offset();
+        let (input, invalid) = E::decode(&self.bytes[self.start..self.end], self.eof);
+        let state = if self.eof && invalid.is_none() {
+            State::new(input)
+        } else {
+            State::partial(input)
+        };
+        let needed = match parse(state) {
+            Ok((v, r)) => {
+                self.start += r.offset();
+                return Ok(Step::Done(v));
+            }
+            Err(e) => match (e.needed(), invalid) {
+                (Some(_), Some(at)) => return Err(ReadError::Encoding(offset + at)),
+                (Some(needed), None) => needed,
+                (None, _) => return Err(ReadError::Parse(e.shifted(offset))),
+            },
+        };
+        let buffered = self.end - self.start;
+        if buffered >= CHUNK {
+            self.want(needed.max(buffered));
+        } else {
+            self.want(needed);
+        }
+        Ok(Step::Pending)
+    }
+
+    /// Room to read the bytes still wanted into, dropping consumed ones first
+    /// when they make up most of the buffer. The buffer at most doubles per
+    /// call, so a length prefix announcing gigabytes only costs memory as the
+    /// bytes actually arrive.
+    pub(crate) fn spare(&mut self) -> &mut [u8] {
+        if self.start > 0 && self.start >= self.end / 2 {
+            self.bytes.copy_within(self.start..self.end, 0);
+            self.discarded += self.start;
+            self.end -= self.start;
+            self.start = 0;
+        }
+        let room = self.wanted.clamp(CHUNK, self.bytes.len().max(CHUNK));
+        if self.bytes.len() < self.end + room {
+            self.bytes.resize(self.end + room, 0);
+        }
+        &mut self.bytes[self.end..]
+    }
+
+    /// Records that `n` bytes were read into `spare`, zero meaning the source
+    /// has ended.
+    pub(crate) fn filled(&mut self, n: usize) {
+        if n == 0 {
+            self.eof = true;
+        }
+        self.end += n;
+        self.wanted = self.wanted.saturating_sub(n);
+    }
+}
+
+/// Drives parsers over a `std::io::Read` source without loading all of it.
+///
+/// Parsers run in partial mode over what has been read so far; whenever one
+/// comes back incomplete the reader pulls in more and tries again, and once
+/// the source is exhausted the last attempt runs in complete mode. Consumed
+/// bytes are dropped from the buffer, so memory stays proportional to the
+/// largest single value rather than the whole source; a source that's one
+/// big value gets buffered whole. Error offsets count from the start of the
+/// source.
+///
+/// Parsers are passed as closures over `State` (`|s| parser.parse_at(s)`) so
+/// they can borrow each fresh view of the buffer.
+pub struct Reader<R, E = Text> {
+    inner: R,
+    buffer: Buffer,
+    encoding: PhantomData<E>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn text(inner: R) -> Self {
+        Reader::new(inner)
+    }
+}
+
+impl<R: Read> Reader<R, Bytes> {
+    pub fn bytes(inner: R) -> Self {
+        Reader::new(inner)
+    }
+}
+
+impl<R: Read, E: Encoding> Reader<R, E> {
+    pub fn new(inner: R) -> Self {
+        Reader {
+            inner,
+            buffer: Buffer::new(),
+            encoding: PhantomData,
+        }
+    }
+
+    /// Offset in the source of the first byte not yet consumed.
+    pub fn offset(&self) -> usize {
+        self.buffer.offset()
+    }
+
+    /// Runs `parse` once over the rest of the source. Add `end()` to the
+    /// parser to insist that nothing follows.
+    pub fn parse<T, F>(&mut self, mut parse: F) -> Result<T, ReadError>
+    where
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        self.step(&mut parse)
+    }
+
+    /// Runs `parse` over and over, one record at a time, until the source is
+    /// used up or a record fails. A record that consumes nothing fails with
+    /// `ReadError::Empty`, since the next one would start from the same place.
+    pub fn records<T, F>(&mut self, parse: F) -> Records<'_, R, E, F>
+    where
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        Records {
+            reader: self,
+            parse,
+            failed: false,
+        }
+    }
+
+    fn step<T, F>(&mut self, parse: &mut F) -> Result<T, ReadError>
+    where
+        F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+    {
+        loop {
+            match self.buffer.run::<E, _, _>(parse)? {
+                Step::Done(v) => return Ok(v),
+                Step::Pending => self.fill()?,
+            }
+        }
+    }
+
+    /// Whether every byte of the source has been read and consumed.
+    fn at_end(&mut self) -> io::Result<bool> {
+        if self.buffer.is_empty() && !self.buffer.is_eof() {
+            self.buffer.want(1);
+            self.fill()?;
+        }
+        Ok(self.buffer.is_empty() && self.buffer.is_eof())
+    }
+
+    /// Reads until the buffer has what it wants or the source runs dry.
+    fn fill(&mut self) -> io::Result<()> {
+        while self.buffer.wanted() > 0 {
+            match self.inner.read(self.buffer.spare()) {
+                Ok(n) => self.buffer.filled(n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the records of a `Reader`, see `Reader::records`.
+pub struct Records<'r, R, E, F> {
+    reader: &'r mut Reader<R, E>,
+    parse: F,
+    failed: bool,
+}
+
+impl<R: Read, E: Encoding, T, F> Iterator for Records<'_, R, E, F>
+where
+    F: for<'b> FnMut(State<'b, E::Input<'b>>) -> ParseResult<'b, E::Input<'b>, T>,
+{
+    type Item = Result<T, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = match self.reader.at_end() {
+            Ok(true) => return None,
+            Ok(false) => {
+                let start = self.reader.offset();
+                let result = self.reader.step(&mut self.parse);
+                result.and_then(|v| self.reader.buffer.advanced(start, v))
+            }
+            Err(e) => Err(e.into()),
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::{be_u16, be_u32, length_data};
+    use crate::helper::*;
+    use crate::Parser;
+
+    /// Hands out its data a few bytes at a time, like a slow pipe.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_text_records() {
+        let data = "héllo wörld ünïcode ";
+        for step in 1..4 {
+            let mut reader = Reader::text(Trickle {
+                data: data.as_bytes(),
+                step,
+            });
+            let words: Vec<String> = reader
+                .records(|s| {
+                    satisfy(|c: &char| *c != ' ')
+                        .many1()
+                        .and_then(character(' '))
+                        .map(|(cs, _)| cs.into_iter().collect())
+                        .parse_at(s)
+                })
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(vec!["héllo", "wörld", "ünïcode"], words);
+        }
+    }
+
+    #[test]
+    fn test_invalid_after_records() {
+        for step in 1..4 {
+            let mut reader = Reader::text(Trickle {
+                data: b"12,34,\xff",
+                step,
+            });
+            let mut numbers = reader.records(|s| {
+                digit()
+                    .many1()
+                    .and_then(character(','))
+                    .map(|(ds, _)| ds.len())
+                    .parse_at(s)
+            });
+            assert_eq!(2, numbers.next().unwrap().unwrap());
+            assert_eq!(2, numbers.next().unwrap().unwrap());
+            let err = numbers.next().unwrap().unwrap_err();
+            assert!(matches!(err, ReadError::Encoding(6)));
+            assert!(numbers.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_empty_record() {
+        let mut reader = Reader::text(Trickle {
+            data: b"12abc",
+            step: 2,
+        });
+        let mut numbers = reader.records(|s| {
+            take_while(char::is_ascii_digit)
+                .map(str::to_string)
+                .parse_at(s)
+        });
+        assert_eq!("12", numbers.next().unwrap().unwrap());
+        let err = numbers.next().unwrap().unwrap_err();
+        assert!(matches!(err, ReadError::Empty(2)));
+        assert!(numbers.next().is_none());
+    }
+
+    #[test]
+    fn test_byte_records() {
+        let data = [0, 2, b'h', b'i', 0, 3, b'y', b'o', b'u', 0];
+        let mut reader = Reader::bytes(Trickle {
+            data: &data,
+            step: 1,
+        });
+        let mut frames = reader.records(|s| length_data(be_u16()).map(<[u8]>::to_vec).parse_at(s));
+        assert_eq!(b"hi".to_vec(), frames.next().unwrap().unwrap());
+        assert_eq!(b"you".to_vec(), frames.next().unwrap().unwrap());
+        let err = frames.next().unwrap().unwrap_err();
+        assert!(matches!(err, ReadError::Parse(e) if e.offset() == 9));
+        assert!(frames.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_to_end() {
+        let data = b"1234";
+        let mut reader = Reader::text(Trickle { data, step: 3 });
+        let (digits, _) = reader
+            .parse(|s| digit().many1().and_then(end()).parse_at(s))
+            .unwrap();
+        assert_eq!(vec![1, 2, 3, 4], digits);
+        let data = b"12 34";
+        let mut reader = Reader::text(Trickle { data, step: 1 });
+        let err = reader
+            .parse(|s| digit().many1().and_then(end()).parse_at(s))
+            .unwrap_err();
+        assert!(matches!(err, ReadError::Parse(e) if e.offset() == 2));
+    }
+
+    #[test]
+    fn test_large_value() {
+        let data = "7".repeat(64 * CHUNK);
+        let mut reader = Reader::text(Trickle {
+            data: data.as_bytes(),
+            step: CHUNK,
+        });
+        let mut runs = 0;
+        let digits = reader
+            .parse(|s| {
+                runs += 1;
+                digit().skip().many1().and_then(end()).parse_at(s)
+            })
+            .unwrap();
+        assert_eq!(64 * CHUNK, digits.0.len());
+        // One run per chunk would be 64; doubling the read needs about log2(64).
+        assert!(runs <= 10, "parsed {} times", runs);
+    }
+
+    #[test]
+    fn test_huge_length_prefix() {
+        let data = [0xff, 0xff, 0xff, 0xff, 1, 2, 3];
+        let mut reader = Reader::bytes(Trickle {
+            data: &data,
+            step: 2,
+        });
+        let err = reader
+            .parse(|s| length_data(be_u32()).map(<[u8]>::len).parse_at(s))
+            .unwrap_err();
+        assert!(matches!(err, ReadError::Parse(e) if e.offset() == 4));
+        // The prefix announces 4 GiB, but only a few bytes ever arrived.
+        assert!(reader.buffer.bytes.len() < 4 * CHUNK);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{Expected, Input, ParseError};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// A position in the original input.
+///
+/// Parsers receive the whole source together with the byte offset they should
+/// start at, so they can report where a value or an error came from instead of
+/// only seeing the remaining suffix.
+///
+/// A partial state is the beginning of an input that's still arriving, so
+/// running off its end means "need more" rather than "failed".
+///
+/// When parsing in recovery mode the state also knows how many of the errors
+/// in `diagnostics` were recovered from on the way to this position. Parsers
+/// only ever run depth first, so an earlier state that gets resumed after
+/// backtracking simply forgets the errors recorded after it.
+///
+/// Every new state starts a fresh parse run with its own id, so memoizing
+/// parsers can tell results for this input apart from those of earlier runs.
+///
+/// A state can also carry a hint: the error of an alternative that was given
+/// up on right here, such as the element that ended a `many`. Consuming input
+/// drops it; failing before that merges it into the failure, so the error
+/// says everything that could have come next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct State<'a, I> {
+    src: I,
+    offset: usize,
+    partial: bool,
+    run: usize,
+    diagnostics: Option<&'a RefCell<Vec<ParseError>>>,
+    recovered: usize,
+    hint: Option<Rc<ParseError>>,
+}
+
+impl<'a, I: Input> State<'a, I> {
+    pub fn new(src: I) -> Self {
+        State {
+            src,
+            offset: 0,
+            partial: false,
+            run: RUNS.fetch_add(1, Ordering::Relaxed),
+            diagnostics: None,
+            recovered: 0,
+            hint: None,
+        }
+    }
+
+    pub fn partial(src: I) -> Self {
+        State {
+            partial: true,
+            ..State::new(src)
+        }
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.partial
+    }
+
+    /// The error for running out of input here: incomplete when more input may
+    /// still arrive, a plain failure otherwise.
+    pub fn exhausted(&self, needed: usize, expected: Vec<Expected>) -> ParseError {
+        if self.partial {
+            ParseError::incomplete(self.offset, needed)
+        } else {
+            ParseError::new(self.offset, expected)
+        }
+    }
+
+    pub(crate) fn recovering(src: I, diagnostics: &'a RefCell<Vec<ParseError>>) -> Self {
+        State {
+            diagnostics: Some(diagnostics),
+            ..State::new(src)
+        }
+    }
+
+    pub fn is_recovering(&self) -> bool {
+        self.diagnostics.is_some()
+    }
+
+    pub(crate) fn record(self, error: ParseError) -> Self {
+        if let Some(diagnostics) = self.diagnostics {
+            let mut diagnostics = diagnostics.borrow_mut();
+            diagnostics.truncate(self.recovered);
+            diagnostics.push(error.after_recording(0));
+        }
+        State {
+            recovered: self.recovered + 1,
+            ..self
+        }
+    }
+
+    /// The errors recovered from between `earlier` and this state.
+    pub(crate) fn recorded_since(&self, earlier: &Self) -> Vec<ParseError> {
+        self.diagnostics.map_or(Vec::new(), |diagnostics| {
+            diagnostics.borrow()[earlier.recovered..self.recovered].to_vec()
+        })
+    }
+
+    pub(crate) fn run(&self) -> usize {
+        self.run
+    }
+
+    pub(crate) fn recovered(&self) -> Vec<ParseError> {
+        self.diagnostics.map_or(Vec::new(), |diagnostics| {
+            diagnostics.borrow()[..self.recovered].to_vec()
+        })
+    }
+
+    pub fn source(&self) -> I {
+        self.src
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn rest(&self) -> I {
+        self.src.slice(self.offset, self.src.input_len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offset == self.src.input_len()
+    }
+
+    /// The next item and the state just past it.
+    pub fn next_item(&self) -> Option<(I::Item, Self)> {
+        self.src
+            .item_at(self.offset)
+            .map(|(item, width)| (item, self.clone().advance(width)))
+    }
+
+    pub fn advance(self, n: usize) -> Self {
+        if n == 0 {
+            return self;
+        }
+        State {
+            offset: self.offset + n,
+            hint: None,
+            ..self
+        }
+    }
+
+    /// `error`, merged with the hint of this state if it has one. Use it for
+    /// the failure of a parser started here.
+    pub fn fail(&self, error: ParseError) -> ParseError {
+        let recorded = error.recorded().max(self.recovered);
+        let error = error.after_recording(recorded);
+        match &self.hint {
+            Some(hint) => (**hint).clone().merge(error),
+            None => error,
+        }
+    }
+
+    /// `error`, from a parser started here that was backtracked out of: the
+    /// errors it recovered from are no longer on the path being parsed.
+    pub(crate) fn abandon(&self, error: ParseError) -> ParseError {
+        error.after_recording(self.recovered)
+    }
+
+    /// Gives up on a parser that failed with `error` when started here: this
+    /// state again, remembering `error` as a hint, or `error` itself when it
+    /// can't be backtracked out of.
+    pub fn backtrack(self, error: ParseError) -> Result<Self, ParseError> {
+        let error = self.fail(error);
+        if error.is_fatal() {
+            return Err(error);
+        }
+        Ok(State {
+            hint: Some(Rc::new(self.abandon(error))),
+            ..self
+        })
+    }
+
+    /// Renames what this state's hint expects, if it's for `offset`.
+    pub(crate) fn relabel(mut self, offset: usize, expected: &Expected) -> Self {
+        if let Some(hint) = self.hint.take() {
+            let hint = if hint.offset() == offset {
+                Rc::new((*hint).clone().expecting(expected.clone()))
+            } else {
+                hint
+            };
+            self.hint = Some(hint);
+        }
+        self
+    }
+
+    /// Marks this state as the end of the rule `name`: a failure here is
+    /// reported as coming after it, in place of whatever the rule itself still
+    /// expected.
+    pub(crate) fn after(mut self, name: &'static str) -> Self {
+        if self.hint.as_ref().is_none_or(|h| h.offset() == self.offset) {
+            let marker = ParseError::new(self.offset, Vec::new()).following(name);
+            self.hint = Some(Rc::new(marker));
+        }
+        self
+    }
+
+    pub(crate) fn hint(&self) -> Option<Rc<ParseError>> {
+        self.hint.clone()
+    }
+
+    pub(crate) fn with_hint(self, hint: Option<Rc<ParseError>>) -> Self {
+        State { hint, ..self }
+    }
+
+    pub fn line_column(&self) -> (usize, usize) {
+        self.src.line_column(self.offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}